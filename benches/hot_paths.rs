@@ -0,0 +1,112 @@
+//! Benchmarks for the FFT, scaling and band transform hot paths.
+//!
+//! Run with `cargo bench`. Every benchmark uses the same synthetic input on
+//! every run, and criterion compares each run against the last one, so a
+//! change that slows a hot path down is reported as a regression. Save a
+//! named baseline with `cargo bench -- --save-baseline <name>` and compare
+//! against it later with `--baseline <name>`.
+#[macro_use]
+extern crate criterion;
+extern crate libc;
+extern crate rusty_bars;
+
+use std::f64::consts::PI;
+use criterion::{black_box, BenchmarkId, Criterion};
+use libc::{c_int, c_char, c_short};
+
+use rusty_bars::fftw::audio::AudioFft;
+use rusty_bars::ncurses::window::Screen;
+use rusty_bars::visualizer::{scale_fft_output, BinReduce, Visualizer};
+
+
+/// A screen that throws away everything drawn on it
+struct NullScreen;
+
+
+impl Screen for NullScreen {
+    fn addstr(&mut self, _: c_int, _: c_int, _: &str) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn addbytes(&mut self, _: c_int, _: c_int, _: &[c_char]) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn noutrefresh(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn doupdate(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn get_max_yx(&self) -> Result<(c_int, c_int), c_int> {
+        Ok((50, 201))
+    }
+
+    fn getch(&mut self) -> Option<c_int> {
+        None
+    }
+
+    fn color_set(&mut self, _: c_short) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+}
+
+
+/// Interleaved S16LE stereo audio with a few tones in it
+fn synthetic_audio(frames: usize) -> Vec<i16> {
+    let mut samples: Vec<i16> = Vec::with_capacity(frames * 2);
+    for i in 0..frames {
+        let t = i as f64 / 44100.0;
+        let value = 0.5 * (2.0 * PI * 440.0 * t).sin()
+            + 0.25 * (2.0 * PI * 1250.0 * t).sin()
+            + 0.125 * (2.0 * PI * 5000.0 * t).sin();
+        let sample = (value * 16384.0) as i16;
+        samples.push(sample);
+        samples.push(sample);
+    }
+    samples
+}
+
+
+/// A smooth, deterministic spectrum shaped roughly like music
+fn synthetic_spectrum(len: usize) -> Vec<f64> {
+    (0..len).map(|i| 60.0 - 20.0 * ((i + 1) as f64).log10() + 5.0 * (i as f64 / 7.0).sin()).collect()
+}
+
+
+fn bench_execute(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execute");
+    for &fft_size in [256, 1024, 4096].iter() {
+        let audio = synthetic_audio(fft_size);
+        let mut fft = AudioFft::new(fft_size, 2);
+        group.bench_function(BenchmarkId::from_parameter(fft_size), |b| b.iter(|| {
+            fft.feed_data(black_box(&audio[..])).unwrap();
+            fft.execute();
+            fft.compute_output();
+        }));
+    }
+    group.finish();
+}
+
+
+fn bench_scale_fft_output(c: &mut Criterion) {
+    let spectrum = synthetic_spectrum(2048);
+    c.bench_function("scale_fft_output (2048 -> 200)", |b| b.iter(|| {
+        scale_fft_output(black_box(&spectrum[..]), 200, BinReduce::Average)
+    }));
+}
+
+
+fn bench_compute_bands(c: &mut Criterion) {
+    let spectrum = synthetic_spectrum(2048);
+    let viz = Visualizer::with_screen(NullScreen);
+    c.bench_function("compute_bands (2048 -> 200x50)", |b| b.iter(|| {
+        viz.compute_bar_heights(&[black_box(&spectrum[..])], 200, 50).unwrap()
+    }));
+}
+
+
+criterion_group!(benches, bench_execute, bench_scale_fft_output, bench_compute_bands);
+criterion_main!(benches);
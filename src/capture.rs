@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+
+/// The audio captured so far, shared between the two ends of the handoff
+struct CaptureRing {
+    /// The raw bytes that haven't been taken by the render thread yet
+    buffer: VecDeque<u8>,
+    /// The most bytes held before the oldest are dropped
+    capacity: usize,
+    /// The size in bytes of one frame (a sample for every channel). Bytes are
+    /// only ever dropped a whole frame at a time so the channels stay aligned.
+    frame_size: usize,
+    /// Set once the sender has gone away
+    closed: bool
+}
+
+
+struct Shared {
+    ring: Mutex<CaptureRing>,
+    ready: Condvar
+}
+
+
+/// Create the two ends of a handoff from the capture thread to the render
+/// thread. Raw audio bytes are pushed into the sender on the capture thread;
+/// the receiver lives on the render thread alongside the AudioFft and the
+/// Visualizer, so neither of those ever has to cross threads.
+///
+/// The buffer holds at most capacity bytes (rounded down to whole frames). If
+/// the render thread falls behind, the oldest audio is dropped instead of
+/// stalling capture.
+pub fn capture_ring(capacity: usize, frame_size: usize) -> (CaptureSender, CaptureReceiver) {
+    let frame_size = frame_size.max(1);
+    let shared = Arc::new(Shared {
+        ring: Mutex::new(CaptureRing {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity: capacity - capacity % frame_size,
+            frame_size: frame_size,
+            closed: false
+        }),
+        ready: Condvar::new()
+    });
+    (CaptureSender { shared: shared.clone() }, CaptureReceiver { shared: shared })
+}
+
+
+/// The capture end of the handoff. Dropping it wakes the receiver up and
+/// tells it no more audio is coming.
+pub struct CaptureSender {
+    shared: Arc<Shared>
+}
+
+
+impl CaptureSender {
+    /// Add captured audio to the buffer. Never blocks on the render thread
+    /// for longer than it takes to copy the bytes in.
+    pub fn push(&self, data: &[u8]) {
+        let mut ring = self.shared.ring.lock().unwrap();
+        ring.buffer.extend(data.iter().cloned());
+
+        if ring.buffer.len() > ring.capacity {
+            let excess = ring.buffer.len() - ring.capacity;
+            // Round up to a whole frame
+            let drop_count = excess.div_ceil(ring.frame_size) * ring.frame_size;
+            let drop_count = drop_count.min(ring.buffer.len());
+            ring.buffer.drain(..drop_count);
+        }
+        self.shared.ready.notify_one();
+    }
+}
+
+
+impl Drop for CaptureSender {
+    fn drop(&mut self) {
+        self.shared.ring.lock().unwrap().closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+
+/// The render end of the handoff
+pub struct CaptureReceiver {
+    shared: Arc<Shared>
+}
+
+
+impl CaptureReceiver {
+    /// Wait until at least len bytes have been captured, then take the most
+    /// recent len bytes. Anything older is thrown away, since the render
+    /// thread only ever wants to draw the latest audio.
+    ///
+    /// Returns None once the sender is gone and there isn't a whole window
+    /// left to take.
+    pub fn recv_window(&self, len: usize) -> Option<Vec<u8>> {
+        let mut ring = self.shared.ring.lock().unwrap();
+        while ring.buffer.len() < len {
+            if ring.closed {
+                return None;
+            }
+            ring = self.shared.ready.wait(ring).unwrap();
+        }
+
+        let stale = ring.buffer.len() - len;
+        ring.buffer.drain(..stale);
+        Some(ring.buffer.drain(..).collect())
+    }
+}
+
+
+#[test]
+fn test_recv_window_takes_latest() {
+    let (sender, receiver) = capture_ring(64, 2);
+    sender.push(&[1, 2, 3, 4]);
+    sender.push(&[5, 6, 7, 8]);
+    assert_eq!(receiver.recv_window(4), Some(vec![5, 6, 7, 8]));
+
+    // Full frames are dropped once the capacity is passed
+    let (sender, receiver) = capture_ring(5, 2);
+    sender.push(&[1, 2, 3, 4, 5, 6]);
+    assert_eq!(receiver.recv_window(2), Some(vec![5, 6]));
+    sender.push(&[1, 2, 3, 4, 5, 6]);
+    drop(sender);
+    assert_eq!(receiver.recv_window(4), Some(vec![3, 4, 5, 6]));
+    assert_eq!(receiver.recv_window(4), None);
+}
+
+
+#[test]
+fn test_handoff_across_threads() {
+    use std::thread;
+
+    let (sender, receiver) = capture_ring(1024, 4);
+    let capture = thread::spawn(move || {
+        for i in 0..100u8 {
+            sender.push(&[i; 16]);
+        }
+    });
+
+    let mut windows = Vec::new();
+    while let Some(window) = receiver.recv_window(16) {
+        assert_eq!(window.len(), 16);
+        windows.push(window);
+    }
+    capture.join().unwrap();
+
+    // Some windows may have been skipped, but each one is whole and the last
+    // push is always delivered
+    assert!(!windows.is_empty());
+    assert_eq!(windows[windows.len() - 1], vec![99u8; 16]);
+    for window in windows.iter() {
+        assert!(window.iter().all(|&x| x == window[0]));
+    }
+}
@@ -0,0 +1,164 @@
+extern crate libc;
+
+use self::libc::c_short;
+use ncurses::window::{COLOR_RED, COLOR_YELLOW, COLOR_GREEN, COLOR_CYAN, COLOR_BLUE, COLOR_MAGENTA};
+
+
+/// An RGB color
+pub type Rgb = (u8, u8, u8);
+
+
+/// The terminal colors the rainbow sweeps through from bass to treble. Only
+/// the basic colors are used so it works on any color terminal. Color pair
+/// i + 1 draws in color i.
+pub const RAINBOW_COLORS: [c_short; 6] = [COLOR_RED, COLOR_YELLOW, COLOR_GREEN, COLOR_CYAN, COLOR_BLUE, COLOR_MAGENTA];
+
+
+/// The ways the bars can be colored
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ColorScheme {
+    /// Every part of a bar is white
+    Monochrome,
+    /// Bars go from green at the bottom through yellow to red at the top, like
+    /// the level meter on a mixing desk
+    Amplitude,
+    /// Each column gets a hue from its position across the screen, sweeping
+    /// from red in the bass to magenta in the treble. Taller parts of a bar
+    /// are brighter in images.
+    FrequencyRainbow
+}
+
+
+impl ColorScheme {
+    /// Get the color of the cell in the given row and column of a bar, where
+    /// rows are counted from the bottom of a window that is height rows tall
+    /// and width columns wide
+    pub fn get_color(&self, row: usize, height: usize, column: usize, width: usize) -> Rgb {
+        let level = fraction(row, height);
+        match *self {
+            ColorScheme::Monochrome => (255, 255, 255),
+            ColorScheme::FrequencyRainbow => {
+                let hue = fraction(column, width) * 300.0;
+                hue_to_rgb(hue, 0.5 + 0.5 * level)
+            },
+            ColorScheme::Amplitude => {
+                if level < 0.5 {
+                    ((level * 2.0 * 255.0) as u8, 255, 0)
+                } else {
+                    (255, ((1.0 - level) * 2.0 * 255.0) as u8, 0)
+                }
+            }
+        }
+    }
+}
+
+
+/// Get how far index is through a range of len items, from 0 for the first
+/// to 1 for the last
+fn fraction(index: usize, len: usize) -> f64 {
+    if len > 1 {
+        index as f64 / (len - 1) as f64
+    } else {
+        0.0
+    }
+}
+
+
+/// Turn a hue in degrees into a fully saturated color at the given brightness
+/// (0 to 1)
+fn hue_to_rgb(hue: f64, brightness: f64) -> Rgb {
+    let sector = (hue / 60.0) % 6.0;
+    let rising = sector % 1.0;
+    let (r, g, b) = match sector as usize {
+        0 => (1.0, rising, 0.0),
+        1 => (1.0 - rising, 1.0, 0.0),
+        2 => (0.0, 1.0, rising),
+        3 => (0.0, 1.0 - rising, 1.0),
+        4 => (rising, 0.0, 1.0),
+        _ => (1.0, 0.0, 1.0 - rising)
+    };
+    let scale = 255.0 * brightness;
+    ((r * scale).round() as u8, (g * scale).round() as u8, (b * scale).round() as u8)
+}
+
+
+/// Get the color pair for a column of a window that is width columns wide in
+/// the FrequencyRainbow scheme. The pairs are 1 to RAINBOW_COLORS.len(),
+/// spread evenly across the width.
+pub fn rainbow_pair(column: usize, width: usize) -> c_short {
+    let count = RAINBOW_COLORS.len();
+    if width == 0 {
+        return 1;
+    }
+    let index = column * count / width;
+    (if index < count { index } else { count - 1 }) as c_short + 1
+}
+
+
+/// Get the color pair for a cell of a spectrogram drawn at level out of
+/// levels, where 0 is silent. Frequency already runs across a spectrogram,
+/// so every scheme colors it by level: Amplitude goes from green through
+/// yellow to red as the cell gets louder, and FrequencyRainbow sweeps back
+/// through the rainbow from magenta to red. Monochrome uses pair 0, the
+/// terminal's own color.
+pub fn level_pair(scheme: ColorScheme, level: usize, levels: usize) -> c_short {
+    match scheme {
+        ColorScheme::Monochrome => 0,
+        ColorScheme::Amplitude => {
+            let level = fraction(level, levels);
+            let color = if level < 1.0 / 3.0 {
+                COLOR_GREEN
+            } else if level < 2.0 / 3.0 {
+                COLOR_YELLOW
+            } else {
+                COLOR_RED
+            };
+            RAINBOW_COLORS.iter().position(|&c| c == color).unwrap() as c_short + 1
+        },
+        ColorScheme::FrequencyRainbow => rainbow_pair(levels.saturating_sub(level + 1), levels)
+    }
+}
+
+
+#[test]
+fn test_amplitude_colors() {
+    assert_eq!(ColorScheme::Amplitude.get_color(0, 11, 0, 1), (0, 255, 0));
+    assert_eq!(ColorScheme::Amplitude.get_color(5, 11, 0, 1), (255, 255, 0));
+    assert_eq!(ColorScheme::Amplitude.get_color(10, 11, 0, 1), (255, 0, 0));
+    assert_eq!(ColorScheme::Amplitude.get_color(0, 1, 0, 1), (0, 255, 0));
+}
+
+
+#[test]
+fn test_rainbow_colors() {
+    // Red in the bass and magenta in the treble, brighter higher up
+    assert_eq!(ColorScheme::FrequencyRainbow.get_color(10, 11, 0, 61), (255, 0, 0));
+    assert_eq!(ColorScheme::FrequencyRainbow.get_color(10, 11, 60, 61), (255, 0, 255));
+    assert_eq!(ColorScheme::FrequencyRainbow.get_color(0, 11, 0, 61), (128, 0, 0));
+}
+
+
+#[test]
+fn test_rainbow_pair() {
+    // 12 columns split into 6 pairs of 2
+    let pairs: Vec<c_short> = (0..12).map(|column| rainbow_pair(column, 12)).collect();
+    assert_eq!(pairs, vec![1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6]);
+    // Fewer columns than colors skips some colors
+    assert_eq!(rainbow_pair(0, 3), 1);
+    assert_eq!(rainbow_pair(2, 3), 5);
+    assert_eq!(rainbow_pair(5, 0), 1);
+}
+
+
+#[test]
+fn test_level_pair() {
+    // Green when quiet, yellow in the middle and red when loud
+    let pairs: Vec<c_short> = (0..10).map(|level| level_pair(ColorScheme::Amplitude, level, 10)).collect();
+    assert_eq!(pairs, vec![3, 3, 3, 2, 2, 2, 1, 1, 1, 1]);
+    // The rainbow runs backwards, from magenta to red
+    assert_eq!(level_pair(ColorScheme::FrequencyRainbow, 0, 10), 6);
+    assert_eq!(level_pair(ColorScheme::FrequencyRainbow, 9, 10), 1);
+    assert_eq!(level_pair(ColorScheme::Monochrome, 9, 10), 0);
+}
@@ -0,0 +1,607 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use color::ColorScheme;
+use fftw::audio::{AudioFft, OutputScale, DEFAULT_DB_FLOOR};
+use fftw::window::WindowFunction;
+use visualizer::{BandSpacing, BinReduce, Layout, Visualizer};
+use ncurses::window::Screen;
+
+
+/// The settings a user can tune while the visualizer runs, so they can be
+/// saved and picked up again next time.
+///
+/// The file format is a small subset of TOML: one `key = value` per line,
+/// with `#` comments. Settings missing from a file keep their defaults and
+/// unknown settings are ignored, so files from older and newer versions both
+/// load.
+///
+/// With the serde feature, the config can also be serialized and deserialized
+/// with serde, in TOML, JSON or any other format, for programs that keep it
+/// alongside their own settings. Missing fields keep their defaults there too.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VisualizerConfig {
+    pub window: WindowFunction,
+    pub color_scheme: ColorScheme,
+    pub spectrum_smoothing: usize,
+    pub band_count: Option<usize>,
+    pub bar_gap: usize,
+    pub layout: Layout,
+    pub bin_reduce: BinReduce,
+    pub include_dc: bool,
+    pub band_spacing: BandSpacing,
+    pub fill_empty_bands: bool,
+    pub db_range: Option<(f64, f64)>,
+    pub log_range: Option<(f64, f64)>,
+    pub silence_threshold: Option<f64>,
+    pub freq_range: Option<(f64, f64)>,
+    pub hysteresis: Option<f64>,
+    pub border_char: char,
+    pub show_gaps: bool,
+    pub pre_emphasis: Option<f64>,
+    pub output_scale: OutputScale,
+    pub peak_hold: Option<f64>,
+    pub peak_attack: Option<f64>,
+    pub clip_hold: Option<usize>,
+    pub gain: f64,
+    pub db_floor: f64,
+    pub amplitude_correction: bool,
+    pub coherent_gain_correction: bool,
+    pub debug_overlay: bool
+}
+
+
+/// Errors that can occur while loading or saving a config
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read or written
+    Io(io::Error),
+    /// The line (the first field, counting from 1) couldn't be understood.
+    /// The second field says why.
+    Parse(usize, String)
+}
+
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+
+impl Default for VisualizerConfig {
+    fn default() -> VisualizerConfig {
+        VisualizerConfig {
+            window: WindowFunction::Hann,
+            color_scheme: ColorScheme::Monochrome,
+            spectrum_smoothing: 0,
+            band_count: None,
+            bar_gap: 0,
+            layout: Layout::Bars,
+            bin_reduce: BinReduce::Average,
+            include_dc: true,
+            band_spacing: BandSpacing::Linear,
+            fill_empty_bands: true,
+            db_range: None,
+            log_range: None,
+            silence_threshold: None,
+            freq_range: None,
+            hysteresis: None,
+            border_char: ' ',
+            show_gaps: false,
+            pre_emphasis: None,
+            output_scale: OutputScale::Decibels,
+            peak_hold: None,
+            peak_attack: None,
+            clip_hold: None,
+            gain: 1.0,
+            db_floor: DEFAULT_DB_FLOOR,
+            amplitude_correction: false,
+            coherent_gain_correction: false,
+            debug_overlay: false
+        }
+    }
+}
+
+
+impl VisualizerConfig {
+    /// Capture the current settings of a visualizer and its FFT
+    pub fn capture<S: Screen>(viz: &Visualizer<S>, fft: &AudioFft) -> VisualizerConfig {
+        VisualizerConfig {
+            window: fft.get_window_function(),
+            color_scheme: viz.get_color_scheme(),
+            spectrum_smoothing: viz.get_spectrum_smoothing(),
+            band_count: viz.get_fixed_band_count(),
+            bar_gap: viz.get_bar_gap(),
+            layout: viz.get_layout(),
+            bin_reduce: viz.get_bin_reduce(),
+            include_dc: viz.get_include_dc(),
+            band_spacing: viz.get_band_spacing(),
+            fill_empty_bands: viz.get_fill_empty_bands(),
+            db_range: viz.get_db_range(),
+            log_range: viz.get_log_range(),
+            silence_threshold: viz.get_silence_threshold(),
+            freq_range: viz.get_freq_range(),
+            hysteresis: viz.get_hysteresis(),
+            border_char: viz.get_border_char(),
+            show_gaps: viz.get_show_gaps(),
+            pre_emphasis: fft.get_pre_emphasis(),
+            output_scale: fft.get_output_scale(),
+            peak_hold: viz.get_peak_hold(),
+            peak_attack: viz.get_peak_attack(),
+            clip_hold: viz.get_clip_hold(),
+            gain: fft.get_gain(),
+            db_floor: fft.get_db_floor(),
+            amplitude_correction: fft.get_amplitude_correction(),
+            coherent_gain_correction: fft.get_coherent_gain_correction(),
+            debug_overlay: viz.get_debug_overlay()
+        }
+    }
+
+    /// Apply the settings to a visualizer and its FFT
+    pub fn apply<S: Screen>(&self, viz: &mut Visualizer<S>, fft: &mut AudioFft) {
+        fft.set_window_function(self.window);
+        viz.set_color_scheme(self.color_scheme);
+        viz.set_spectrum_smoothing(self.spectrum_smoothing);
+        viz.set_band_count(self.band_count);
+        viz.set_bar_gap(self.bar_gap);
+        viz.set_layout(self.layout);
+        viz.set_bin_reduce(self.bin_reduce);
+        viz.set_include_dc(self.include_dc);
+        viz.set_band_spacing(self.band_spacing);
+        viz.set_fill_empty_bands(self.fill_empty_bands);
+        match self.db_range {
+            Some((floor, ceiling)) => viz.set_db_range(floor, ceiling),
+            None => viz.clear_db_range()
+        }
+        match self.log_range {
+            Some((floor, ceiling)) => viz.set_log_range(floor, ceiling),
+            None => viz.clear_log_range()
+        }
+        match self.silence_threshold {
+            Some(db) => viz.set_silence_threshold(db),
+            None => viz.clear_silence_threshold()
+        }
+        match self.freq_range {
+            Some((low, high)) => viz.set_freq_range(low, high),
+            None => viz.clear_freq_range()
+        }
+        viz.set_hysteresis(self.hysteresis);
+        viz.set_border_char(self.border_char);
+        viz.set_show_gaps(self.show_gaps);
+        fft.set_pre_emphasis(self.pre_emphasis);
+        fft.set_output_scale(self.output_scale);
+        fft.set_gain(self.gain);
+        fft.set_db_floor(self.db_floor);
+        fft.set_amplitude_correction(self.amplitude_correction);
+        fft.set_coherent_gain_correction(self.coherent_gain_correction);
+        viz.set_silence_level(fft.get_silence_level());
+        viz.set_peak_hold(self.peak_hold);
+        viz.set_peak_attack(self.peak_attack);
+        viz.set_clip_hold(self.clip_hold);
+        viz.set_debug_overlay(self.debug_overlay);
+    }
+
+    /// Read a config from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<VisualizerConfig, ConfigError> {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        VisualizerConfig::parse(&text[..])
+    }
+
+    /// Write the config to a file, replacing whatever was there. The
+    /// directory it goes in is created if it doesn't exist yet.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        if let Some(dir) = path.as_ref().parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let mut file = try!(File::create(path));
+        try!(write!(file, "{}", self));
+        Ok(())
+    }
+
+    /// Read a config from the text of a config file
+    pub fn parse(text: &str) -> Result<VisualizerConfig, ConfigError> {
+        let mut config = VisualizerConfig::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |reason: &str| ConfigError::Parse(index + 1, reason.to_string());
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(error("expected key = value"))
+            };
+
+            match key {
+                "window" => {
+                    config.window = match (unquote(value), config.window) {
+                        (Some("hann"), _) => WindowFunction::Hann,
+                        (Some("rectangular"), _) => WindowFunction::Rectangular,
+                        (Some("flat_top"), _) => WindowFunction::FlatTop,
+                        (Some("blackman_harris"), _) => WindowFunction::BlackmanHarris,
+                        (Some("nuttall"), _) => WindowFunction::Nuttall,
+                        (Some("bartlett"), _) => WindowFunction::Bartlett,
+                        (Some("welch"), _) => WindowFunction::Welch,
+                        // Keep a beta that was read first
+                        (Some("kaiser"), WindowFunction::Kaiser(beta)) => WindowFunction::Kaiser(beta),
+                        (Some("kaiser"), _) => WindowFunction::Kaiser(8.6),
+                        _ => return Err(error("unknown window"))
+                    };
+                },
+                "kaiser_beta" => {
+                    let beta = try!(value.parse::<f64>().map_err(|_| error("expected a number")));
+                    config.window = match config.window {
+                        WindowFunction::Kaiser(_) => WindowFunction::Kaiser(beta),
+                        window => window
+                    };
+                },
+                "color_scheme" => {
+                    config.color_scheme = match unquote(value) {
+                        Some("monochrome") => ColorScheme::Monochrome,
+                        Some("amplitude") => ColorScheme::Amplitude,
+                        Some("frequency_rainbow") => ColorScheme::FrequencyRainbow,
+                        _ => return Err(error("unknown color scheme"))
+                    };
+                },
+                "spectrum_smoothing" => {
+                    config.spectrum_smoothing = try!(value.parse().map_err(|_| error("expected a whole number")));
+                },
+                "band_count" => {
+                    config.band_count = Some(try!(value.parse().map_err(|_| error("expected a whole number"))));
+                },
+                "bar_gap" => {
+                    config.bar_gap = try!(value.parse().map_err(|_| error("expected a whole number")));
+                },
+                "layout" => {
+                    config.layout = match unquote(value) {
+                        Some("bars") => Layout::Bars,
+                        Some("stereo_split") => Layout::StereoSplit,
+                        Some("mirror") => Layout::Mirror,
+                        Some("top_down") => Layout::TopDown,
+                        Some("mono_mirror") => Layout::MonoMirror,
+                        _ => return Err(error("unknown layout"))
+                    };
+                },
+                "bin_reduce" => {
+                    config.bin_reduce = match unquote(value) {
+                        Some("average") => BinReduce::Average,
+                        Some("max") => BinReduce::Max,
+                        Some("sum") => BinReduce::Sum,
+                        Some("energy") => BinReduce::Energy,
+                        _ => return Err(error("unknown bin reduce"))
+                    };
+                },
+                "include_dc" => {
+                    config.include_dc = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                "band_spacing" => {
+                    config.band_spacing = match unquote(value) {
+                        Some("linear") => BandSpacing::Linear,
+                        Some("logarithmic") => BandSpacing::Logarithmic,
+                        _ => return Err(error("unknown band spacing"))
+                    };
+                },
+                "fill_empty_bands" => {
+                    config.fill_empty_bands = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                "db_range" => {
+                    config.db_range = match parse_pair(value) {
+                        Some(range) => Some(range),
+                        None => return Err(error("expected [floor, ceiling]"))
+                    };
+                },
+                "log_range" => {
+                    config.log_range = match parse_pair(value) {
+                        Some(range) => Some(range),
+                        None => return Err(error("expected [floor, ceiling]"))
+                    };
+                },
+                "silence_threshold" => {
+                    config.silence_threshold = Some(try!(value.parse().map_err(|_| error("expected a number"))));
+                },
+                "freq_range" => {
+                    config.freq_range = match parse_pair(value) {
+                        Some(range) => Some(range),
+                        None => return Err(error("expected [low, high]"))
+                    };
+                },
+                "hysteresis" => {
+                    config.hysteresis = Some(try!(value.parse().map_err(|_| error("expected a number"))));
+                },
+                "border_char" => {
+                    // A quote or a backslash is escaped with a backslash
+                    let text = unquote(value).unwrap_or("");
+                    let text = text.strip_prefix('\\').unwrap_or(text);
+                    let mut chars = text.chars();
+                    config.border_char = match (chars.next(), chars.next()) {
+                        (Some(c), None) if c == ' ' || c.is_ascii_graphic() => c,
+                        _ => return Err(error("expected a single printable ASCII character"))
+                    };
+                },
+                "show_gaps" => {
+                    config.show_gaps = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                "pre_emphasis" => {
+                    config.pre_emphasis = Some(try!(value.parse().map_err(|_| error("expected a number"))));
+                },
+                "output_scale" => {
+                    config.output_scale = match unquote(value) {
+                        Some("decibels") => OutputScale::Decibels,
+                        Some("magnitude") => OutputScale::Magnitude,
+                        Some("power") => OutputScale::Power,
+                        _ => return Err(error("unknown output scale"))
+                    };
+                },
+                "peak_hold" => {
+                    config.peak_hold = Some(try!(value.parse().map_err(|_| error("expected a number"))));
+                },
+                "peak_attack" => {
+                    config.peak_attack = Some(try!(value.parse().map_err(|_| error("expected a number"))));
+                },
+                "clip_hold" => {
+                    config.clip_hold = Some(try!(value.parse().map_err(|_| error("expected a whole number"))));
+                },
+                "gain" => {
+                    config.gain = try!(value.parse().map_err(|_| error("expected a number")));
+                },
+                "db_floor" => {
+                    config.db_floor = try!(value.parse().map_err(|_| error("expected a number")));
+                },
+                "amplitude_correction" => {
+                    config.amplitude_correction = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                "coherent_gain_correction" => {
+                    config.coherent_gain_correction = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                "debug_overlay" => {
+                    config.debug_overlay = try!(value.parse().map_err(|_| error("expected true or false")));
+                },
+                _ => {}
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+
+/// Writes the config in the file format
+impl fmt::Display for VisualizerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let window = match self.window {
+            WindowFunction::Hann => "hann",
+            WindowFunction::Rectangular => "rectangular",
+            WindowFunction::FlatTop => "flat_top",
+            WindowFunction::BlackmanHarris => "blackman_harris",
+            WindowFunction::Nuttall => "nuttall",
+            WindowFunction::Bartlett => "bartlett",
+            WindowFunction::Welch => "welch",
+            WindowFunction::Kaiser(_) => "kaiser"
+        };
+        try!(writeln!(f, "window = \"{}\"", window));
+        if let WindowFunction::Kaiser(beta) = self.window {
+            try!(writeln!(f, "kaiser_beta = {:?}", beta));
+        }
+
+        let color_scheme = match self.color_scheme {
+            ColorScheme::Monochrome => "monochrome",
+            ColorScheme::Amplitude => "amplitude",
+            ColorScheme::FrequencyRainbow => "frequency_rainbow"
+        };
+        try!(writeln!(f, "color_scheme = \"{}\"", color_scheme));
+        try!(writeln!(f, "spectrum_smoothing = {}", self.spectrum_smoothing));
+        if let Some(band_count) = self.band_count {
+            try!(writeln!(f, "band_count = {}", band_count));
+        }
+        try!(writeln!(f, "bar_gap = {}", self.bar_gap));
+
+        let layout = match self.layout {
+            Layout::Bars => "bars",
+            Layout::StereoSplit => "stereo_split",
+            Layout::Mirror => "mirror",
+            Layout::TopDown => "top_down",
+            Layout::MonoMirror => "mono_mirror"
+        };
+        try!(writeln!(f, "layout = \"{}\"", layout));
+
+        let bin_reduce = match self.bin_reduce {
+            BinReduce::Average => "average",
+            BinReduce::Max => "max",
+            BinReduce::Sum => "sum",
+            BinReduce::Energy => "energy"
+        };
+        try!(writeln!(f, "bin_reduce = \"{}\"", bin_reduce));
+        try!(writeln!(f, "include_dc = {}", self.include_dc));
+
+        let band_spacing = match self.band_spacing {
+            BandSpacing::Linear => "linear",
+            BandSpacing::Logarithmic => "logarithmic"
+        };
+        try!(writeln!(f, "band_spacing = \"{}\"", band_spacing));
+        try!(writeln!(f, "fill_empty_bands = {}", self.fill_empty_bands));
+        if let Some((floor, ceiling)) = self.db_range {
+            try!(writeln!(f, "db_range = [{:?}, {:?}]", floor, ceiling));
+        }
+        if let Some((floor, ceiling)) = self.log_range {
+            try!(writeln!(f, "log_range = [{:?}, {:?}]", floor, ceiling));
+        }
+        if let Some(db) = self.silence_threshold {
+            try!(writeln!(f, "silence_threshold = {:?}", db));
+        }
+        if let Some((low, high)) = self.freq_range {
+            try!(writeln!(f, "freq_range = [{:?}, {:?}]", low, high));
+        }
+        if let Some(deadband) = self.hysteresis {
+            try!(writeln!(f, "hysteresis = {:?}", deadband));
+        }
+        try!(writeln!(f, "border_char = \"{}\"", self.border_char.escape_default()));
+        try!(writeln!(f, "show_gaps = {}", self.show_gaps));
+        if let Some(alpha) = self.pre_emphasis {
+            try!(writeln!(f, "pre_emphasis = {:?}", alpha));
+        }
+
+        let output_scale = match self.output_scale {
+            OutputScale::Decibels => "decibels",
+            OutputScale::Magnitude => "magnitude",
+            OutputScale::Power => "power"
+        };
+        try!(writeln!(f, "output_scale = \"{}\"", output_scale));
+        if let Some(decay) = self.peak_hold {
+            try!(writeln!(f, "peak_hold = {:?}", decay));
+        }
+        if let Some(attack) = self.peak_attack {
+            try!(writeln!(f, "peak_attack = {:?}", attack));
+        }
+        if let Some(frames) = self.clip_hold {
+            try!(writeln!(f, "clip_hold = {}", frames));
+        }
+        try!(writeln!(f, "gain = {:?}", self.gain));
+        try!(writeln!(f, "db_floor = {:?}", self.db_floor));
+        try!(writeln!(f, "amplitude_correction = {}", self.amplitude_correction));
+        try!(writeln!(f, "coherent_gain_correction = {}", self.coherent_gain_correction));
+        try!(writeln!(f, "debug_overlay = {}", self.debug_overlay));
+        Ok(())
+    }
+}
+
+
+/// Get where the config is kept: ~/.config/rusty_bars.toml. Returns None if
+/// there's no home directory.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".config").join("rusty_bars.toml"))
+}
+
+
+/// Cut a # comment off the end of a line, leaving any # inside a quoted
+/// string alone
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+
+/// Get the text inside a quoted string, or None if it isn't quoted
+fn unquote(value: &str) -> Option<&str> {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        Some(&value[1..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+
+/// Get the two numbers in an array like [1.5, 2], or None if it isn't one
+fn parse_pair(value: &str) -> Option<(f64, f64)> {
+    let numbers: Vec<Option<f64>> = value.trim_start_matches('[').trim_end_matches(']')
+        .split(',')
+        .map(|number| number.trim().parse().ok())
+        .collect();
+    match &numbers[..] {
+        [Some(first), Some(second)] => Some((*first, *second)),
+        _ => None
+    }
+}
+
+
+#[test]
+fn test_round_trip() {
+    let config = VisualizerConfig {
+        window: WindowFunction::Kaiser(5.5),
+        color_scheme: ColorScheme::FrequencyRainbow,
+        spectrum_smoothing: 3,
+        band_count: Some(16),
+        bar_gap: 2,
+        layout: Layout::TopDown,
+        bin_reduce: BinReduce::Energy,
+        include_dc: false,
+        band_spacing: BandSpacing::Logarithmic,
+        fill_empty_bands: false,
+        db_range: Some((-80.0, -6.5)),
+        log_range: Some((0.5, 2e6)),
+        silence_threshold: Some(-60.0),
+        freq_range: Some((40.0, 16000.0)),
+        hysteresis: Some(0.4),
+        border_char: '#',
+        show_gaps: true,
+        pre_emphasis: Some(0.95),
+        output_scale: OutputScale::Power,
+        peak_hold: Some(0.5),
+        peak_attack: Some(2.0),
+        clip_hold: Some(30),
+        gain: 2.5,
+        db_floor: -96.0,
+        amplitude_correction: true,
+        coherent_gain_correction: true,
+        debug_overlay: true
+    };
+    let text = config.to_string();
+    assert_eq!(VisualizerConfig::parse(&text[..]).unwrap(), config);
+
+    // Quotes and backslashes are escaped
+    for &border_char in ['"', '\\'].iter() {
+        let config = VisualizerConfig { border_char: border_char, ..VisualizerConfig::default() };
+        let text = config.to_string();
+        assert_eq!(VisualizerConfig::parse(&text[..]).unwrap(), config);
+    }
+}
+
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    extern crate toml;
+
+    let mut config = VisualizerConfig::default();
+    config.window = WindowFunction::Kaiser(5.5);
+    config.layout = Layout::StereoSplit;
+    config.db_range = Some((-80.0, -6.5));
+    config.border_char = '|';
+    config.output_scale = OutputScale::Magnitude;
+    let text = toml::to_string(&config).unwrap();
+    assert_eq!(toml::from_str::<VisualizerConfig>(&text[..]).unwrap(), config);
+
+    // Missing fields keep their defaults
+    let config: VisualizerConfig = toml::from_str("layout = \"mirror\"").unwrap();
+    assert_eq!(config.layout, Layout::Mirror);
+    assert_eq!(config.window, WindowFunction::Hann);
+}
+
+
+#[test]
+fn test_missing_and_unknown_settings() {
+    let text = "# An old config\nlayout = \"mirror\"\nsome_new_setting = 12\n";
+    let config = VisualizerConfig::parse(text).unwrap();
+    assert_eq!(config.layout, Layout::Mirror);
+    assert_eq!(config.window, WindowFunction::Hann);
+    assert_eq!(config.band_count, None);
+
+    // A # inside quotes isn't a comment
+    let config = VisualizerConfig::parse("border_char = \"#\" # the border\n").unwrap();
+    assert_eq!(config.border_char, '#');
+
+    match VisualizerConfig::parse("\nbar_gap = wide") {
+        Err(ConfigError::Parse(2, _)) => {},
+        other => panic!("unexpected result {:?}", other)
+    }
+}
@@ -0,0 +1,98 @@
+extern crate libc;
+
+use self::libc::c_int;
+use color::ColorScheme;
+use fftw::window::WindowFunction;
+
+
+/// The beta used for the Kaiser window when cycling through windows
+const CYCLE_KAISER_BETA: f64 = 8.6;
+
+
+/// The things the user can do with the keyboard while the visualizer runs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Switch to the next window function
+    CycleWindow,
+    /// Switch to the next color scheme
+    CycleColors,
+    /// Pause or unpause the animation
+    TogglePause,
+    /// Smooth the spectrum across more frequency bins
+    IncreaseSmoothing,
+    /// Smooth the spectrum across fewer frequency bins
+    DecreaseSmoothing,
+    /// Save the current settings so they're used next time
+    SaveConfig
+}
+
+
+/// Get the action bound to a key, if there is one
+pub fn key_action(key: c_int) -> Option<Action> {
+    if !(0..=127).contains(&key) {
+        return None;
+    }
+
+    match key as u8 as char {
+        'w' => Some(Action::CycleWindow),
+        'c' => Some(Action::CycleColors),
+        ' ' => Some(Action::TogglePause),
+        '+' | '=' => Some(Action::IncreaseSmoothing),
+        '-' => Some(Action::DecreaseSmoothing),
+        's' => Some(Action::SaveConfig),
+        _ => None
+    }
+}
+
+
+/// Get the window function after this one when cycling through them
+pub fn next_window(window: WindowFunction) -> WindowFunction {
+    match window {
+        WindowFunction::Hann => WindowFunction::Rectangular,
+        WindowFunction::Rectangular => WindowFunction::FlatTop,
+        WindowFunction::FlatTop => WindowFunction::BlackmanHarris,
+        WindowFunction::BlackmanHarris => WindowFunction::Nuttall,
+        WindowFunction::Nuttall => WindowFunction::Bartlett,
+        WindowFunction::Bartlett => WindowFunction::Welch,
+        WindowFunction::Welch => WindowFunction::Kaiser(CYCLE_KAISER_BETA),
+        WindowFunction::Kaiser(_) => WindowFunction::Hann
+    }
+}
+
+
+/// Get the color scheme after this one when cycling through them
+pub fn next_color_scheme(color_scheme: ColorScheme) -> ColorScheme {
+    match color_scheme {
+        ColorScheme::Monochrome => ColorScheme::Amplitude,
+        ColorScheme::Amplitude => ColorScheme::FrequencyRainbow,
+        ColorScheme::FrequencyRainbow => ColorScheme::Monochrome
+    }
+}
+
+
+#[test]
+fn test_key_action() {
+    assert_eq!(key_action('w' as c_int), Some(Action::CycleWindow));
+    assert_eq!(key_action('c' as c_int), Some(Action::CycleColors));
+    assert_eq!(key_action(' ' as c_int), Some(Action::TogglePause));
+    assert_eq!(key_action('+' as c_int), Some(Action::IncreaseSmoothing));
+    assert_eq!(key_action('=' as c_int), Some(Action::IncreaseSmoothing));
+    assert_eq!(key_action('-' as c_int), Some(Action::DecreaseSmoothing));
+    assert_eq!(key_action('s' as c_int), Some(Action::SaveConfig));
+    assert_eq!(key_action('x' as c_int), None);
+    assert_eq!(key_action(-1), None);
+    assert_eq!(key_action(0o632), None);
+}
+
+
+#[test]
+fn test_cycles_wrap_around() {
+    let mut window = WindowFunction::Hann;
+    for _ in 0..8 {
+        window = next_window(window);
+    }
+    assert_eq!(window, WindowFunction::Hann);
+
+    let colors = next_color_scheme(next_color_scheme(next_color_scheme(ColorScheme::Monochrome)));
+    assert_eq!(colors, ColorScheme::Monochrome);
+}
@@ -0,0 +1,60 @@
+/// Follows a target value that changes every frame, rising towards it by at
+/// most attack each frame and falling towards it by at most release. A rate
+/// of None moves straight to the target. This is how the peak markers trail
+/// behind their bars.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Envelope {
+    /// The most the value can rise in one frame, or None for no limit
+    pub attack: Option<f64>,
+    /// The most the value can fall in one frame, or None for no limit
+    pub release: Option<f64>,
+    /// The current value
+    pub value: f64
+}
+
+
+impl Envelope {
+    /// Create a new Envelope starting at value
+    pub fn new(attack: Option<f64>, release: Option<f64>, value: f64) -> Envelope {
+        Envelope {
+            attack: attack,
+            release: release,
+            value: value
+        }
+    }
+
+    /// Move the value a frame's worth towards target and return it
+    pub fn process(&mut self, target: f64) -> f64 {
+        self.value = if target >= self.value {
+            match self.attack {
+                Some(rate) => (self.value + rate).min(target),
+                None => target
+            }
+        } else {
+            match self.release {
+                Some(rate) => (self.value - rate).max(target),
+                None => target
+            }
+        };
+        self.value
+    }
+}
+
+
+#[test]
+fn test_rise_and_fall() {
+    // Jumps straight up, then falls one step a frame
+    let mut envelope = Envelope::new(None, Some(1.0), 0.0);
+    let values: Vec<f64> = [2.0, 6.0, 6.0, 3.0, 1.0, 1.0, 1.0].iter()
+        .map(|&target| envelope.process(target))
+        .collect();
+    assert_eq!(values, vec![2.0, 6.0, 6.0, 5.0, 4.0, 3.0, 2.0]);
+
+    // Climbs at the attack rate and stops at the target
+    let mut envelope = Envelope::new(Some(1.5), None, 2.0);
+    assert_eq!(envelope.process(6.0), 3.5);
+    assert_eq!(envelope.process(6.0), 5.0);
+    assert_eq!(envelope.process(6.0), 6.0);
+    // With no release rate it drops straight back down
+    assert_eq!(envelope.process(0.0), 0.0);
+}
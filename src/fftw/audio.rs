@@ -1,15 +1,98 @@
-use std::slice;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
 //use std::num::Float;
-use fftw::multichannel::MultiChannelFft;
-use fftw::hanning::HanningWindowCalculator;
+use fftw::multichannel::{MultiChannelFft, DEFAULT_PARALLEL_THRESHOLD};
+use fftw::peaks;
+use fftw::plan::is_power_of_two;
+use fftw::resample::Resampler;
+use fftw::sample::Sample;
+use fftw::window::{WindowCalculator, WindowFunction};
 
 
-/// Audio FFT for 16bit little endian audio data (S16LE)
-pub struct AudioFft {
+/// Errors that can occur while handling audio data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FftError {
+    /// The number of values given (the first field) isn't a multiple of the
+    /// number of channels (the second field)
+    UnevenChannels(usize, usize),
+    /// The FFT size isn't a power of two
+    InvalidFftSize(usize),
+    /// There has to be at least one channel
+    NoChannels,
+    /// The number of bytes given (the first field) isn't a whole number of
+    /// frames of the frame size in bytes (the second field)
+    PartialFrame(usize, usize),
+    /// Only interleaved audio can be resampled
+    PlanarResampling,
+    /// A sample rate to resample between isn't finite and above 0
+    InvalidSampleRate
+}
+
+
+/// How the samples of each channel are arranged in the audio data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelLayout {
+    /// One sample for each channel at a time: L R L R L R. This is what
+    /// PulseAudio gives us and is the default.
+    Interleaved,
+    /// All of the first channel's samples, then all of the second channel's
+    /// samples: L L L R R R
+    Planar
+}
+
+
+impl ChannelLayout {
+    /// Get the channel and the index within that channel of the value at
+    /// position in a buffer holding channel_len samples for each of
+    /// channel_count channels
+    fn locate(&self, position: usize, channel_count: usize, channel_len: usize) -> (usize, usize) {
+        match *self {
+            ChannelLayout::Interleaved => (position % channel_count, position / channel_count),
+            ChannelLayout::Planar => (position / channel_len, position % channel_len)
+        }
+    }
+}
+
+
+/// The lowest level in dB the output goes down to unless told otherwise
+pub const DEFAULT_DB_FLOOR: f64 = -120.0;
+
+
+/// The largest sample value, on the 16 bit scale every sample type is
+/// converted to. Gain clips the signal here.
+const FULL_SCALE: f64 = 32768.0;
+
+
+/// Turn a magnitude into decibels, clamping it so the result is never below
+/// floor. Silent bins would otherwise be -inf and subnormal ones would be
+/// hundreds of dB down, swamping everything else in the frame.
+pub fn amplitude_to_db(magnitude: f64, floor: f64) -> f64 {
+    let min_magnitude = 10.0f64.powf(floor / 20.0);
+    20.0 * magnitude.max(min_magnitude).log10()
+}
+
+
+/// The units the output is computed in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OutputScale {
+    /// 20 * log10(magnitude). This is the default.
+    Decibels,
+    /// The linear magnitude of each bin
+    Magnitude,
+    /// The power of each bin, which is the magnitude squared
+    Power
+}
+
+
+/// Audio FFT for little endian audio data of the sample type S. The default is
+/// 16bit little endian audio data (S16LE).
+pub struct AudioFft<S: Sample = i16> {
     /// The multichannel fft object that does the work for us
     multichan_fft: MultiChannelFft,
     /// The input cursor indicates how much data has been read in. Input is
-    /// 16bit integers, inerleaved by channels. The so that means the maximum
+    /// samples of type S, inerleaved by channels. The so that means the maximum
     /// value of input_cursor is channel_count * fft_size
     input_cursor: usize,
     /// The number of elements needed to fill an FFT. This is equal to the size
@@ -19,18 +102,46 @@ pub struct AudioFft {
     fft_size: usize,
     /// The number of audio channels. Ex: 2 for stereo audio.
     channel_count: usize,
-    /// Helper for executing the Hanning window function as data is inserted
-    hanning: HanningWindowCalculator,
+    /// How the channels are arranged in the input
+    channel_layout: ChannelLayout,
+    /// The linear gain applied to every sample before anything else
+    gain: f64,
+    /// The alpha of the pre-emphasis filter, if it is enabled
+    pre_emphasis: Option<f64>,
+    /// The last sample fed in for each channel, for the pre-emphasis filter.
+    /// This carries over between FFTs so there is no discontinuity between
+    /// them.
+    previous_samples: Vec<f64>,
+    /// Helper for executing the window function as data is inserted
+    window: WindowCalculator,
+    /// Whether to correct the output for the FFT size and the window's
+    /// coherent gain so that it measures the true amplitude of tones
+    amplitude_correction: bool,
+    /// Whether to divide the output by the window's coherent gain alone, so
+    /// levels don't depend on the window function
+    coherent_gain_correction: bool,
+    /// The units to compute the output in
+    output_scale: OutputScale,
+    /// The lowest level in dB the output can have
+    db_floor: f64,
+    /// Converts the input to the analysis rate before it is windowed, if set
+    resampler: Option<Resampler>,
+    /// Resampled values that didn't fit in the FFT yet
+    resampled: VecDeque<f64>,
     /// Holds output for the combined channels
-    output: Vec<f64>
+    output: Vec<f64>,
+    /// Holds output for each individual channel
+    channel_outputs: Vec<Vec<f64>>,
+    /// The type of sample being decoded
+    sample_type: PhantomData<S>
 }
 
 
-impl AudioFft {
+impl<S: Sample> AudioFft<S> {
     /// Create a new AudioFft
-    pub fn new(fft_size: usize, channel_count: usize) -> AudioFft {
-        let mut out_vec = Vec::with_capacity(fft_size/2);
-        for _ in 0..fft_size/2 {
+    pub fn new(fft_size: usize, channel_count: usize) -> AudioFft<S> {
+        let mut out_vec = Vec::with_capacity(fft_size/2 + 1);
+        for _ in 0..fft_size/2 + 1 {
             out_vec.push(0.0);
         }
         AudioFft {
@@ -38,12 +149,194 @@ impl AudioFft {
             input_cursor: 0,
             fft_size: fft_size,
             channel_count: channel_count,
+            channel_layout: ChannelLayout::Interleaved,
             required_input: channel_count * fft_size,
-            hanning: HanningWindowCalculator::new(fft_size),
+            gain: 1.0,
+            pre_emphasis: None,
+            previous_samples: vec![0.0; channel_count],
+            window: WindowCalculator::new(WindowFunction::Hann, fft_size),
+            amplitude_correction: false,
+            coherent_gain_correction: false,
+            output_scale: OutputScale::Decibels,
+            db_floor: DEFAULT_DB_FLOOR,
+            resampler: None,
+            resampled: VecDeque::new(),
+            channel_outputs: vec![out_vec.clone(); channel_count],
             output: out_vec,
+            sample_type: PhantomData
+        }
+    }
+
+    /// Change the size of the FFT. The old plans are destroyed and new ones
+    /// are planned with freshly allocated buffers and window multipliers. The
+    /// channel, layout, window and output settings all carry over, but any
+    /// data fed in since the last execution is thrown away. Fails without
+    /// changing anything if the new size isn't a power of two.
+    pub fn resize(&mut self, fft_size: usize) -> Result<(), FftError> {
+        if !is_power_of_two(fft_size) {
+            return Err(FftError::InvalidFftSize(fft_size));
+        }
+        if fft_size == self.fft_size {
+            return Ok(());
+        }
+
+        let parallel_threshold = self.multichan_fft.get_parallel_threshold();
+        // Drop the old plans before making the new ones
+        self.multichan_fft = MultiChannelFft::new(fft_size, self.channel_count);
+        self.multichan_fft.set_parallel_threshold(parallel_threshold);
+
+        self.fft_size = fft_size;
+        self.required_input = self.channel_count * fft_size;
+        self.input_cursor = 0;
+        self.resampled.clear();
+        if let Some(ref mut resampler) = self.resampler {
+            resampler.reset();
+        }
+        self.window = WindowCalculator::new(self.window.get_function(), fft_size);
+        self.output = vec![0.0; fft_size/2 + 1];
+        self.channel_outputs = vec![self.output.clone(); self.channel_count];
+        Ok(())
+    }
+
+    /// Get the size of the FFT
+    pub fn get_fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    /// Get the number of audio channels
+    pub fn get_channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Set how the channels are arranged in the input. With the planar layout,
+    /// all of the first channel's samples for an FFT are fed in before any of
+    /// the second channel's. Only interleaved input can be resampled, so the
+    /// planar layout is refused while resampling is on.
+    pub fn set_channel_layout(&mut self, layout: ChannelLayout) -> Result<(), FftError> {
+        if layout == ChannelLayout::Planar && self.resampler.is_some() {
+            return Err(FftError::PlanarResampling);
+        }
+        self.channel_layout = layout;
+        Ok(())
+    }
+
+    /// Set the number of channels at which the per-channel FFTs are run on
+    /// separate threads. None keeps them all on the calling thread.
+    pub fn set_parallel_threshold(&mut self, threshold: Option<usize>) {
+        self.multichan_fft.set_parallel_threshold(threshold);
+    }
+
+    /// Get the number of bytes in a frame of audio data, which is one sample
+    /// for every channel
+    pub fn get_frame_size(&self) -> usize {
+        self.channel_count * S::SIZE
+    }
+
+    /// Get the number of bytes of audio data needed to fill the FFT
+    pub fn get_buf_size(&self) -> usize {
+        self.required_input * S::SIZE
+    }
+
+    /// Set the window function applied to the audio. It is applied as data is
+    /// fed in, so it takes effect for the data fed after this call. The
+    /// multipliers are only recomputed if the function (or its parameter)
+    /// changed.
+    pub fn set_window_function(&mut self, function: WindowFunction) {
+        if function != self.window.get_function() {
+            self.window = WindowCalculator::new(function, self.fft_size);
         }
     }
 
+    /// Get the window function applied to the audio
+    pub fn get_window_function(&self) -> WindowFunction {
+        self.window.get_function()
+    }
+
+    /// Multiply every sample by linear_gain as it is fed in, before
+    /// pre-emphasis and windowing. Samples pushed past full scale are clipped
+    /// to it, as an overdriven input would be, so a high gain adds the
+    /// harmonics of clipping to the output. The default of 1.0 leaves the
+    /// samples untouched.
+    pub fn set_gain(&mut self, linear_gain: f64) {
+        self.gain = linear_gain;
+    }
+
+    /// Get the linear gain applied to the samples
+    pub fn get_gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Enable or disable the pre-emphasis filter, y[n] = x[n] - alpha*x[n-1],
+    /// which boosts high frequencies so treble detail isn't drowned out by
+    /// bass. An alpha of around 0.95 is typical. None disables it.
+    pub fn set_pre_emphasis(&mut self, alpha: Option<f64>) {
+        self.pre_emphasis = alpha;
+    }
+
+    /// Get the alpha of the pre-emphasis filter, or None if it's disabled
+    pub fn get_pre_emphasis(&self) -> Option<f64> {
+        self.pre_emphasis
+    }
+
+    /// Enable or disable amplitude correction. When enabled, the output is
+    /// scaled by the FFT size and the window's coherent gain so that a tone
+    /// with an amplitude of A (in 16 bit sample units) measures 20*log10(A)
+    /// regardless of the window function. It is off by default.
+    pub fn set_amplitude_correction(&mut self, enabled: bool) {
+        self.amplitude_correction = enabled;
+    }
+
+    /// Get whether amplitude correction is enabled
+    pub fn get_amplitude_correction(&self) -> bool {
+        self.amplitude_correction
+    }
+
+    /// Enable or disable coherent gain correction. A window scales a tone down
+    /// by its coherent gain (the mean of its multipliers, 0.5 for Hann), so
+    /// the same tone measures differently under different windows. When
+    /// enabled, the output is divided by the coherent gain to undo that, but
+    /// not scaled by the FFT size. Amplitude correction already includes it.
+    /// It is off by default.
+    pub fn set_coherent_gain_correction(&mut self, enabled: bool) {
+        self.coherent_gain_correction = enabled;
+    }
+
+    /// Get whether coherent gain correction is enabled
+    pub fn get_coherent_gain_correction(&self) -> bool {
+        self.coherent_gain_correction
+    }
+
+    /// Set the lowest level in dB the output can have. Quieter bins, including
+    /// silent ones, are raised to it. The default is DEFAULT_DB_FLOOR.
+    pub fn set_db_floor(&mut self, floor: f64) {
+        self.db_floor = floor;
+    }
+
+    /// Get the lowest level in dB the output can have
+    pub fn get_db_floor(&self) -> f64 {
+        self.db_floor
+    }
+
+    /// Set the units the output is computed in. The linear scales are never
+    /// negative, which the visualizer's normalization handles just as well as
+    /// decibels.
+    pub fn set_output_scale(&mut self, output_scale: OutputScale) {
+        self.output_scale = output_scale;
+    }
+
+    /// Get the units the output is computed in
+    pub fn get_output_scale(&self) -> OutputScale {
+        self.output_scale
+    }
+
+    /// Get the output level of a silent bin: the dB floor for decibels, or 0
+    /// for the linear scales
+    pub fn get_silence_level(&self) -> f64 {
+        match self.output_scale {
+            OutputScale::Decibels => self.db_floor,
+            OutputScale::Magnitude | OutputScale::Power => 0.0
+        }
+    }
 
     /// Exeuce the FFT
     pub fn execute(&mut self) {
@@ -51,63 +344,202 @@ impl AudioFft {
         self.input_cursor = 0;
     }
 
+    /// Throw away the data fed in since the last execution without running
+    /// the FFT
+    pub fn discard_input(&mut self) {
+        self.input_cursor = 0;
+        self.resampled.clear();
+        if let Some(ref mut resampler) = self.resampler {
+            resampler.reset();
+        }
+    }
+
+    /// Resample the input from input_rate to analysis_rate before it goes
+    /// into the FFT, so the frequency of each bin depends only on the
+    /// analysis rate and not on the device. Only interleaved input can be
+    /// resampled, so this fails if the channel layout is planar. Both rates
+    /// have to be finite and above 0.
+    pub fn set_resampling(&mut self, input_rate: f64, analysis_rate: f64) -> Result<(), FftError> {
+        if self.channel_layout == ChannelLayout::Planar {
+            return Err(FftError::PlanarResampling);
+        }
+        match Resampler::new(input_rate, analysis_rate, self.channel_count) {
+            Some(resampler) => self.resampler = Some(resampler),
+            None => return Err(FftError::InvalidSampleRate)
+        }
+        self.resampled.clear();
+        Ok(())
+    }
+
+    /// Feed the input straight into the FFT at its own rate
+    pub fn clear_resampling(&mut self) {
+        self.resampler = None;
+        self.resampled.clear();
+    }
+
     /// Allows a client to feed data into the FFT in chunks. This is useful for
     /// ineracting with PulseAudio because its asynchronous API gives audio data
     /// in arbitrary chunk sizes depending on how much data is available.
     ///
     /// Arguments:
-    ///     input: A slice pointing at S16LE audio data for the number of
-    ///            channels in self.channel_count
+    ///     input: A slice of samples for the number of channels in
+    ///            self.channel_count
     /// Returns:
-    ///     The number of bytes it read. If the number of bytes returned is
-    ///     less than the input size, the FFT is ready to execute.
-    pub fn feed_data(&mut self, input: &[i16]) -> usize {
-        let mut bytes_read: usize = 0;
-
-        let mut inputs = self.multichan_fft.get_inputs();
-
-        for value in input.iter() {
-            // If there is enough data to run the FFT, return the number of
-            // bytes that were read out of this input slice
-            if self.input_cursor == self.required_input {
-                return bytes_read;
-            }
+    ///     The number of samples it read. If the number of samples returned
+    ///     is less than the input size, the FFT is ready to execute. Nothing
+    ///     is read if the samples can't be split evenly between the channels.
+    pub fn feed_data(&mut self, input: &[S]) -> Result<usize, FftError> {
+        if self.channel_count == 0 {
+            return Err(FftError::NoChannels);
+        }
+        if !input.len().is_multiple_of(self.channel_count) {
+            return Err(FftError::UnevenChannels(input.len(), self.channel_count));
+        }
+        Ok(self.feed_values(input.iter().map(|value| value.to_f64())))
+    }
+
+    /// Wrapper for feed_data which takes an &[u8] slice of little endian
+    /// audio data instead of a slice of samples. Decodes each sample from the
+    /// bytes as it is fed in. A partial sample at the end of the slice is not
+    /// read.
+    ///
+    /// Returns:
+    ///     The number of bytes it read.
+    pub fn feed_u8_data(&mut self, input: &[u8]) -> usize {
+        let values = input.chunks(S::SIZE)
+            .filter(|bytes| bytes.len() == S::SIZE)
+            .map(|bytes| S::from_le_bytes(bytes).to_f64());
+        self.feed_values(values) * S::SIZE
+    }
+
+    /// Like feed_u8_data, but the audio data has to be a whole number of
+    /// frames (a sample for every channel). A buffer that was cut short would
+    /// otherwise leave the channels out of step for every FFT after it.
+    ///
+    /// Returns:
+    ///     The number of bytes it read, or an error without reading anything
+    ///     if the input ends part way through a frame or there are no
+    ///     channels.
+    pub fn feed_frames(&mut self, input: &[u8]) -> Result<usize, FftError> {
+        let frame_size = self.get_frame_size();
+        if frame_size == 0 {
+            return Err(FftError::NoChannels);
+        }
+        if !input.len().is_multiple_of(frame_size) {
+            return Err(FftError::PartialFrame(input.len(), frame_size));
+        }
+        Ok(self.feed_u8_data(input))
+    }
 
-            // The channel number and index the current value is for
-            let channel_num = self.input_cursor % self.channel_count;
-            let channel_index = self.input_cursor / self.channel_count;
+    /// Feeds decoded sample values into the FFT inputs, resampling them first
+    /// if that's been set up. Returns the number of values read.
+    fn feed_values<I: Iterator<Item=f64>>(&mut self, values: I) -> usize {
+        let mut values_read: usize = 0;
 
-            // Compute the hanning window value for the element set the input
-            inputs[channel_num][channel_index] = self.hanning.get_value(channel_index, *value as f64);
+        if self.resampler.is_none() {
+            for value in values {
+                // If there is enough data to run the FFT, return the number of
+                // values that were read
+                if self.input_cursor == self.required_input {
+                    return values_read;
+                }
+                self.write_value(value);
+                values_read += 1;
+            }
+            return values_read;
+        }
 
-            bytes_read += 1;
-            self.input_cursor += 1;
+        for value in values {
+            // Resampled values left over from the last call go in first
+            if !self.write_resampled() {
+                return values_read;
+            }
+            if let Some(ref mut resampler) = self.resampler {
+                resampler.push(value, &mut self.resampled);
+            }
+            values_read += 1;
         }
+        self.write_resampled();
 
-        bytes_read
+        values_read
     }
 
-    /// Wrapper for feed_data which takes an &[u8] slice instead of an &[i16]
-    /// slice. Simply casts the slice to an i16 slice then calls feed_data on
-    /// it.
-    pub fn feed_u8_data(&mut self, input: &[u8]) -> usize {
-        let i16_ptr: *const i16 = input.as_ptr() as *const i16;
-        self.feed_data(unsafe{ slice::from_raw_parts(i16_ptr, input.len()/2) }) * 2
+    /// Moves as many resampled values into the FFT inputs as will fit.
+    /// Returns false if the FFT is full.
+    fn write_resampled(&mut self) -> bool {
+        while self.input_cursor < self.required_input {
+            match self.resampled.pop_front() {
+                Some(value) => self.write_value(value),
+                None => return true
+            }
+        }
+        false
+    }
+
+    /// Writes the next value into the FFT inputs, applying the gain,
+    /// pre-emphasis and the window function
+    fn write_value(&mut self, value: f64) {
+        // The channel number and index the current value is for
+        let (channel_num, channel_index) = self.channel_layout.locate(
+            self.input_cursor, self.channel_count, self.fft_size);
+
+        let value = if self.gain == 1.0 {
+            value
+        } else {
+            (value * self.gain).clamp(-FULL_SCALE, FULL_SCALE)
+        };
+
+        let value = match self.pre_emphasis {
+            Some(alpha) => {
+                let filtered = value - alpha * self.previous_samples[channel_num];
+                self.previous_samples[channel_num] = value;
+                filtered
+            },
+            None => value
+        };
+
+        // Compute the window function value for the element set the input
+        let input = self.multichan_fft.get_channel_mut(channel_num).unwrap().get_input_slice();
+        input[channel_index] = self.window.get_value(channel_index, value);
+
+        self.input_cursor += 1;
     }
 
-    /// Computes the combined output of all channels into the output field of
-    /// this struct. Every time compute_output is called, it reuses the same
-    /// output vector to avoid allocations.
+    /// Computes the output of each channel and the combined output of all
+    /// channels into the output fields of this struct. Every time
+    /// compute_output is called, it reuses the same output vectors to avoid
+    /// allocations.
     pub fn compute_output(&mut self) {
+        let scale: f64 = if self.amplitude_correction {
+            2.0 / (self.fft_size as f64 * self.window.get_coherent_gain())
+        } else if self.coherent_gain_correction {
+            1.0 / self.window.get_coherent_gain()
+        } else {
+            1.0
+        };
+
+        // The DC and Nyquist bins have no mirror image in the negative
+        // frequencies, so unlike the others they already hold all of their
+        // amplitude and mustn't be doubled
+        let nyquist = self.fft_size / 2;
+        let edge_scale = if self.amplitude_correction { scale / 2.0 } else { scale };
+
         let mut first = true;
-        for channel in self.multichan_fft.channel_plans.iter() {
-            for (index, &value) in channel.get_output_slice()[0..self.fft_size/2].iter().enumerate() {
-                // Turn the FFT output value into decibals
-                let power: f64 = 20.0 * value.abs().log10();
+        for (channel, channel_output) in self.multichan_fft.channel_plans.iter().zip(self.channel_outputs.iter_mut()) {
+            for (index, &value) in channel.get_output_slice().iter().enumerate() {
+                // Turn the FFT output value into the output units
+                let bin_scale = if index == 0 || index == nyquist { edge_scale } else { scale };
+                let magnitude: f64 = value.abs() * bin_scale;
+                let level: f64 = match self.output_scale {
+                    OutputScale::Decibels => amplitude_to_db(magnitude, self.db_floor),
+                    OutputScale::Magnitude => magnitude,
+                    OutputScale::Power => magnitude * magnitude
+                };
+                channel_output[index] = level;
                 // If it's bigger than the biggest value for this channel for
                 // this execution, then replace the current value
-                if first || power > self.output[index] {
-                    self.output[index] = power;
+                if first || level > self.output[index] {
+                    self.output[index] = level;
                 }
             }
             first = false;
@@ -118,4 +550,732 @@ impl AudioFft {
     pub fn get_output(&self) -> &[f64] {
         &self.output[..]
     }
+
+    /// Convert a value of the output into decibels
+    fn to_decibels(&self, level: f64) -> f64 {
+        match self.output_scale {
+            OutputScale::Decibels => level,
+            OutputScale::Magnitude => amplitude_to_db(level, self.db_floor),
+            OutputScale::Power => amplitude_to_db(level.sqrt(), self.db_floor)
+        }
+    }
+
+    /// Get the frequency in Hz of the loudest tone in the combined output,
+    /// ignoring DC. The peak is interpolated between bins, so it is far more
+    /// accurate than the width of a bin.
+    pub fn dominant_frequency(&self, sample_rate: f64) -> Option<f64> {
+        let levels: Vec<f64> = self.output.iter().map(|&x| self.to_decibels(x)).collect();
+        peaks::dominant_bin(&levels[..]).map(|bin| bin * sample_rate / self.fft_size as f64)
+    }
+
+    /// Get up to n of the loudest tones in the combined output as
+    /// (frequency, level) pairs, loudest first. Each tone is a local maximum,
+    /// so the sides of one peak aren't counted as separate tones.
+    pub fn top_peaks(&self, n: usize, sample_rate: f64) -> Vec<(f64, f64)> {
+        let levels: Vec<f64> = self.output.iter().map(|&x| self.to_decibels(x)).collect();
+        peaks::local_maxima(&levels[..]).into_iter().take(n).map(|bin| {
+            let freq = peaks::interpolate_peak(&levels[..], bin) * sample_rate / self.fft_size as f64;
+            (freq, self.output[bin])
+        }).collect()
+    }
+
+    /// Borrow the output vector for a single channel. Returns None if there
+    /// is no channel with that index.
+    pub fn get_channel_output(&self, channel: usize) -> Option<&[f64]> {
+        self.channel_outputs.get(channel).map(|output| &output[..])
+    }
+}
+
+
+/// Collects the settings for an AudioFft and checks they work together before
+/// building it. The sample type is picked with the type parameter, like
+/// AudioFftBuilder::<f32>::new().
+pub struct AudioFftBuilder<S: Sample = i16> {
+    fft_size: usize,
+    channel_count: usize,
+    channel_layout: ChannelLayout,
+    window_function: WindowFunction,
+    gain: f64,
+    pre_emphasis: Option<f64>,
+    amplitude_correction: bool,
+    coherent_gain_correction: bool,
+    output_scale: OutputScale,
+    db_floor: f64,
+    parallel_threshold: Option<usize>,
+    resampling: Option<(f64, f64)>,
+    sample_type: PhantomData<S>
+}
+
+
+impl<S: Sample> Default for AudioFftBuilder<S> {
+    fn default() -> AudioFftBuilder<S> {
+        AudioFftBuilder::new()
+    }
+}
+
+
+impl<S: Sample> AudioFftBuilder<S> {
+    /// Create a new builder with the same defaults as AudioFft::new: a 1024
+    /// point FFT of two interleaved channels with a Hann window, computing
+    /// the output in decibels.
+    pub fn new() -> AudioFftBuilder<S> {
+        AudioFftBuilder {
+            fft_size: 1024,
+            channel_count: 2,
+            channel_layout: ChannelLayout::Interleaved,
+            window_function: WindowFunction::Hann,
+            gain: 1.0,
+            pre_emphasis: None,
+            amplitude_correction: false,
+            coherent_gain_correction: false,
+            output_scale: OutputScale::Decibels,
+            db_floor: DEFAULT_DB_FLOOR,
+            parallel_threshold: Some(DEFAULT_PARALLEL_THRESHOLD),
+            resampling: None,
+            sample_type: PhantomData
+        }
+    }
+
+    /// Set the size of the FFT. It must be a power of two.
+    pub fn fft_size(mut self, fft_size: usize) -> AudioFftBuilder<S> {
+        self.fft_size = fft_size;
+        self
+    }
+
+    /// Set the number of audio channels
+    pub fn channels(mut self, channel_count: usize) -> AudioFftBuilder<S> {
+        self.channel_count = channel_count;
+        self
+    }
+
+    /// Set how the channels are arranged in the input
+    pub fn layout(mut self, layout: ChannelLayout) -> AudioFftBuilder<S> {
+        self.channel_layout = layout;
+        self
+    }
+
+    /// Set the window function
+    pub fn window(mut self, function: WindowFunction) -> AudioFftBuilder<S> {
+        self.window_function = function;
+        self
+    }
+
+    /// Set the linear gain applied to the samples
+    pub fn gain(mut self, linear_gain: f64) -> AudioFftBuilder<S> {
+        self.gain = linear_gain;
+        self
+    }
+
+    /// Set the pre-emphasis coefficient, or None to disable it
+    pub fn pre_emphasis(mut self, alpha: Option<f64>) -> AudioFftBuilder<S> {
+        self.pre_emphasis = alpha;
+        self
+    }
+
+    /// Correct the output for the FFT size and window gain
+    pub fn amplitude_correction(mut self, enabled: bool) -> AudioFftBuilder<S> {
+        self.amplitude_correction = enabled;
+        self
+    }
+
+    /// Correct the output for the window gain alone
+    pub fn coherent_gain_correction(mut self, enabled: bool) -> AudioFftBuilder<S> {
+        self.coherent_gain_correction = enabled;
+        self
+    }
+
+    /// Set the units to compute the output in
+    pub fn output_scale(mut self, output_scale: OutputScale) -> AudioFftBuilder<S> {
+        self.output_scale = output_scale;
+        self
+    }
+
+    /// Set the lowest level in dB the output can have
+    pub fn db_floor(mut self, floor: f64) -> AudioFftBuilder<S> {
+        self.db_floor = floor;
+        self
+    }
+
+    /// Set the number of channels at which the FFTs are run on separate
+    /// threads, or None to keep them on the calling thread
+    pub fn plan(mut self, parallel_threshold: Option<usize>) -> AudioFftBuilder<S> {
+        self.parallel_threshold = parallel_threshold;
+        self
+    }
+
+    /// Resample the input from input_rate to analysis_rate
+    pub fn resampling(mut self, input_rate: f64, analysis_rate: f64) -> AudioFftBuilder<S> {
+        self.resampling = Some((input_rate, analysis_rate));
+        self
+    }
+
+    /// Check the settings and build the AudioFft
+    pub fn build(self) -> Result<AudioFft<S>, FftError> {
+        if !is_power_of_two(self.fft_size) {
+            return Err(FftError::InvalidFftSize(self.fft_size));
+        }
+        if self.channel_count == 0 {
+            return Err(FftError::NoChannels);
+        }
+
+        let mut fft = AudioFft::new(self.fft_size, self.channel_count);
+        try!(fft.set_channel_layout(self.channel_layout));
+        fft.set_window_function(self.window_function);
+        fft.set_gain(self.gain);
+        fft.set_pre_emphasis(self.pre_emphasis);
+        fft.set_amplitude_correction(self.amplitude_correction);
+        fft.set_coherent_gain_correction(self.coherent_gain_correction);
+        fft.set_output_scale(self.output_scale);
+        fft.set_db_floor(self.db_floor);
+        fft.set_parallel_threshold(self.parallel_threshold);
+        if let Some((input_rate, analysis_rate)) = self.resampling {
+            try!(fft.set_resampling(input_rate, analysis_rate));
+        }
+        Ok(fft)
+    }
+}
+
+
+#[cfg(test)]
+fn analyze(signal: &[f64], fft_size: usize) -> Vec<f64> {
+    analyze_with(signal, AudioFft::new(fft_size, 1))
+}
+
+
+/// Runs a signal through an already configured single channel AudioFft and
+/// returns the output
+#[cfg(test)]
+fn analyze_with(signal: &[f64], mut fft: AudioFft) -> Vec<f64> {
+    use fftw::synth::to_s16le;
+
+    let bytes = to_s16le(signal, 1);
+    assert_eq!(fft.feed_u8_data(&bytes[..]), bytes.len());
+    fft.execute();
+    fft.compute_output();
+    fft.get_output().to_vec()
+}
+
+
+/// Get the index of the largest value
+#[cfg(test)]
+fn peak_bin(output: &[f64]) -> usize {
+    let mut peak = 0;
+    for (i, &x) in output.iter().enumerate() {
+        if x > output[peak] {
+            peak = i;
+        }
+    }
+    peak
+}
+
+
+#[test]
+fn test_sine_peak() {
+    use fftw::synth::sine;
+
+    // A tone exactly on bin 100
+    let freq = 100.0 * 44100.0 / 1024.0;
+    let output = analyze(&sine(freq, 0.5, 44100.0, 1024)[..], 1024);
+    assert_eq!(output.len(), 513);
+    assert_eq!(peak_bin(&output[..]), 100);
+
+    // The Hann window spreads the tone into the neighbouring bins, but it
+    // falls off quickly outside of the main lobe
+    assert!(output[100] - output[98] > 20.0);
+    assert!(output[100] - output[102] > 20.0);
+
+    // A tone between bins 200 and 201 peaks on one of them
+    let freq = 200.5 * 44100.0 / 1024.0;
+    let peak = peak_bin(&analyze(&sine(freq, 0.5, 44100.0, 1024)[..], 1024)[..]);
+    assert!(peak == 200 || peak == 201);
+}
+
+
+#[test]
+fn test_two_tones() {
+    use fftw::synth::{mix, sine};
+
+    let low = sine(50.0 * 44100.0 / 1024.0, 0.4, 44100.0, 1024);
+    let high = sine(300.0 * 44100.0 / 1024.0, 0.2, 44100.0, 1024);
+    let output = analyze(&mix(&[&low[..], &high[..]])[..], 1024);
+
+    assert_eq!(peak_bin(&output[..]), 50);
+    assert_eq!(peak_bin(&output[200..]) + 200, 300);
+    // Half the amplitude is about 6dB quieter
+    assert!((output[50] - output[300] - 6.02).abs() < 0.5);
+    // Well away from both tones there is next to nothing
+    assert!(output[50] - output[175] > 60.0);
+}
+
+
+#[test]
+fn test_white_noise_is_flat() {
+    use fftw::synth::white_noise;
+
+    let output = analyze(&white_noise(0.5, 4096, 1)[..], 4096);
+
+    // Compare the average level of each quarter of the spectrum (skipping DC)
+    let quarter = output.len() / 4;
+    let averages: Vec<f64> = (0..4)
+        .map(|q| {
+            let band = &output[q * quarter + 1..(q + 1) * quarter];
+            band.iter().fold(0.0, |sum, &x| sum + x) / band.len() as f64
+        })
+        .collect();
+    for &average in averages.iter() {
+        assert!((average - averages[0]).abs() < 2.0);
+    }
+}
+
+
+#[test]
+fn test_flat_top_amplitude() {
+    use fftw::synth::sine;
+
+    // A tone half way between two bins is where the Hann window's amplitude
+    // error is worst
+    let signal = sine(200.5 * 44100.0 / 1024.0, 0.5, 44100.0, 1024);
+    let expected = 20.0 * (0.5 * 32767.0f64).log10();
+
+    let mut fft = AudioFft::new(1024, 1);
+    fft.set_window_function(WindowFunction::FlatTop);
+    fft.set_amplitude_correction(true);
+    let output = analyze_with(&signal[..], fft);
+    assert!((output[peak_bin(&output[..])] - expected).abs() < 0.1);
+
+    let mut fft = AudioFft::new(1024, 1);
+    fft.set_amplitude_correction(true);
+    let output = analyze_with(&signal[..], fft);
+    assert!(expected - output[peak_bin(&output[..])] > 1.0);
+}
+
+
+#[test]
+fn test_dc_amplitude() {
+    // A constant offset is measured at its own level in the DC bin, without
+    // the doubling the other bins get
+    let signal = vec![0.25; 1024];
+    let expected = 20.0 * (0.25 * 32767.0f64).log10();
+
+    let mut fft = AudioFft::new(1024, 1);
+    fft.set_amplitude_correction(true);
+    let output = analyze_with(&signal[..], fft);
+    assert!((output[0] - expected).abs() < 0.1);
+}
+
+
+#[test]
+fn test_blackman_harris_finds_weak_tone() {
+    use fftw::synth::{mix, sine};
+
+    // A tone 80 dB down, 12 bins away from a loud tone that falls between
+    // bins and so leaks the most
+    let loud = sine(200.5 * 44100.0 / 1024.0, 0.5, 44100.0, 1024);
+    let weak = sine(212.0 * 44100.0 / 1024.0, 0.5e-4, 44100.0, 1024);
+    let signal = mix(&[&loud[..], &weak[..]]);
+
+    let analyze_window = |function| {
+        let mut fft = AudioFft::new(1024, 1);
+        fft.set_window_function(function);
+        analyze_with(&signal[..], fft)
+    };
+    // How far the weak tone's bin stands above the bins either side of it
+    let prominence = |output: &[f64]| output[212] - output[210].max(output[214]);
+
+    let hann = analyze_window(WindowFunction::Hann);
+    let blackman_harris = analyze_window(WindowFunction::BlackmanHarris);
+    // Hann's leakage buries it, but it stands out with Blackman-Harris
+    assert!(prominence(&hann[..]) < 0.0);
+    assert!(prominence(&blackman_harris[..]) > 6.0);
+}
+
+
+#[test]
+fn test_coherent_gain_correction() {
+    use fftw::synth::sine;
+
+    // A tone right on a bin, so neither window loses any of it to scalloping
+    let signal = sine(64.0 * 44100.0 / 1024.0, 0.5, 44100.0, 1024);
+    let peak = |function: WindowFunction, corrected: bool| {
+        let mut fft = AudioFft::new(1024, 1);
+        fft.set_window_function(function);
+        fft.set_coherent_gain_correction(corrected);
+        let output = analyze_with(&signal[..], fft);
+        output[64]
+    };
+
+    // Hann halves the tone, which is 6dB down
+    let difference = peak(WindowFunction::Rectangular, false) - peak(WindowFunction::Hann, false);
+    assert!((difference - 6.0).abs() < 0.1);
+
+    let difference = peak(WindowFunction::Rectangular, true) - peak(WindowFunction::Hann, true);
+    assert!(difference.abs() < 0.1);
+}
+
+
+#[test]
+fn test_feed_frames_rejects_partial_frames() {
+    let mut fft: AudioFft = AudioFft::new(1024, 2);
+    assert_eq!(fft.get_frame_size(), 4);
+
+    let bytes = vec![0u8; fft.get_buf_size() - 1];
+    assert_eq!(fft.feed_frames(&bytes[..]), Err(FftError::PartialFrame(4095, 4)));
+
+    let bytes = vec![0u8; fft.get_buf_size()];
+    assert_eq!(fft.feed_frames(&bytes[..]), Ok(4096));
+
+    // Without any channels there are no frames to feed
+    let mut fft: AudioFft = AudioFft::new(1024, 0);
+    assert_eq!(fft.feed_frames(&bytes[..]), Err(FftError::NoChannels));
+}
+
+
+#[test]
+fn test_buf_size() {
+    assert_eq!(AudioFft::<u8>::new(1024, 2).get_buf_size(), 2048);
+    assert_eq!(AudioFft::<i16>::new(1024, 2).get_buf_size(), 4096);
+    assert_eq!(AudioFft::<i32>::new(1024, 2).get_buf_size(), 8192);
+    assert_eq!(AudioFft::<f32>::new(512, 1).get_buf_size(), 2048);
+}
+
+
+#[test]
+fn test_feed_u8_data_decodes_samples() {
+    use fftw::synth::sine;
+
+    // The same tone as i16 and f32 samples gives the same output
+    let signal = sine(100.0 * 44100.0 / 1024.0, 0.5, 44100.0, 1024);
+    let i16_output = analyze(&signal[..], 1024);
+
+    let mut bytes: Vec<u8> = Vec::new();
+    for &x in signal.iter() {
+        let bits = ((x * 32767.0) as i16 as f32 / 32768.0).to_bits();
+        bytes.extend([bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8].iter());
+    }
+    let mut fft = AudioFft::<f32>::new(1024, 1);
+    assert_eq!(fft.feed_u8_data(&bytes[..]), fft.get_buf_size());
+    fft.execute();
+    fft.compute_output();
+
+    assert!((fft.get_output()[100] - i16_output[100]).abs() < 1e-6);
+}
+
+
+#[test]
+fn test_feed_data_layouts() {
+    let left: Vec<i16> = (0..8).map(|i| i * 100).collect();
+    let right: Vec<i16> = (0..8).map(|i| -i * 50).collect();
+    let interleaved: Vec<i16> = left.iter().zip(right.iter()).flat_map(|(&l, &r)| vec![l, r]).collect();
+    let planar: Vec<i16> = left.iter().chain(right.iter()).cloned().collect();
+
+    let mut interleaved_fft: AudioFft = AudioFft::new(8, 2);
+    assert_eq!(interleaved_fft.feed_data(&interleaved[..]), Ok(16));
+    let mut planar_fft: AudioFft = AudioFft::new(8, 2);
+    planar_fft.set_channel_layout(ChannelLayout::Planar).unwrap();
+    assert_eq!(planar_fft.feed_data(&planar[..]), Ok(16));
+    for channel in 0..2 {
+        assert_eq!(planar_fft.multichan_fft.get_inputs()[channel].to_vec(),
+                   interleaved_fft.multichan_fft.get_inputs()[channel].to_vec());
+    }
+
+    // Samples that don't split evenly between the channels aren't read
+    let mut fft: AudioFft = AudioFft::new(8, 2);
+    assert_eq!(fft.feed_data(&[1, 2, 3]), Err(FftError::UnevenChannels(3, 2)));
+    assert_eq!(fft.input_cursor, 0);
+    let mut fft: AudioFft = AudioFft::new(8, 0);
+    assert_eq!(fft.feed_data(&[1, 2]), Err(FftError::NoChannels));
+}
+
+
+#[test]
+fn test_planar_feed() {
+    use fftw::synth::{sine, to_s16le};
+
+    let left = to_s16le(&sine(1000.0, 0.5, 44100.0, 256)[..], 1);
+    let right = to_s16le(&sine(5000.0, 0.25, 44100.0, 256)[..], 1);
+
+    let mut interleaved: Vec<u8> = Vec::new();
+    for (l, r) in left.chunks(2).zip(right.chunks(2)) {
+        interleaved.extend(l.iter().chain(r.iter()));
+    }
+    let planar: Vec<u8> = left.iter().chain(right.iter()).cloned().collect();
+
+    let mut interleaved_fft: AudioFft = AudioFft::new(256, 2);
+    interleaved_fft.feed_u8_data(&interleaved[..]);
+    interleaved_fft.execute();
+    interleaved_fft.compute_output();
+
+    // Planar data fed in two chunks ends up in the same channels
+    let mut planar_fft: AudioFft = AudioFft::new(256, 2);
+    planar_fft.set_channel_layout(ChannelLayout::Planar).unwrap();
+    planar_fft.feed_u8_data(&planar[..300]);
+    planar_fft.feed_u8_data(&planar[300..]);
+    planar_fft.execute();
+    planar_fft.compute_output();
+
+    for channel in 0..2 {
+        assert_eq!(planar_fft.get_channel_output(channel), interleaved_fft.get_channel_output(channel));
+    }
+}
+
+
+#[test]
+fn test_output_scales_agree() {
+    use fftw::synth::{mix, sine, white_noise};
+
+    let tone = sine(3000.0, 0.5, 44100.0, 512);
+    let noise = white_noise(0.01, 512, 7);
+    let signal = mix(&[&tone[..], &noise[..]]);
+
+    let analyze_scale = |output_scale| {
+        let mut fft = AudioFft::new(512, 1);
+        fft.set_output_scale(output_scale);
+        analyze_with(&signal[..], fft)
+    };
+    let decibels = analyze_scale(OutputScale::Decibels);
+    let magnitude = analyze_scale(OutputScale::Magnitude);
+    let power = analyze_scale(OutputScale::Power);
+
+    for i in 0..decibels.len() {
+        assert!(magnitude[i] >= 0.0);
+        assert!((20.0 * magnitude[i].log10() - decibels[i]).abs() < 1e-9);
+        assert!((magnitude[i] * magnitude[i] - power[i]).abs() <= 1e-9 * power[i]);
+    }
+}
+
+
+#[test]
+fn test_gain() {
+    use fftw::synth::sine;
+
+    let signal = sine(100.0 * 44100.0 / 1024.0, 0.25, 44100.0, 1024);
+    let analyze_gain = |gain| {
+        let mut fft = AudioFft::new(1024, 1);
+        fft.set_output_scale(OutputScale::Magnitude);
+        fft.set_gain(gain);
+        analyze_with(&signal[..], fft)
+    };
+
+    // Doubling the gain doubles the magnitudes
+    let unity = analyze_gain(1.0);
+    let doubled = analyze_gain(2.0);
+    assert!((doubled[100] / unity[100] - 2.0).abs() < 1e-3);
+
+    // Far too much gain clips the quarter scale tone into a square wave. Its
+    // fundamental is only about 5 times louder, not 1000, and it gains odd
+    // harmonics.
+    let clipped = analyze_gain(1000.0);
+    assert!(clipped[100] < unity[100] * 6.0);
+    assert!(clipped[300] > doubled[300] * 100.0);
+}
+
+
+#[test]
+fn test_pre_emphasis_boosts_treble() {
+    use fftw::synth::{mix, sine};
+
+    let low_bin = 12;
+    let high_bin = 230;
+    let low = sine(low_bin as f64 * 44100.0 / 512.0, 0.3, 44100.0, 512);
+    let high = sine(high_bin as f64 * 44100.0 / 512.0, 0.3, 44100.0, 512);
+    let signal = mix(&[&low[..], &high[..]]);
+
+    let plain = analyze(&signal[..], 512);
+    let mut fft = AudioFft::new(512, 1);
+    fft.set_pre_emphasis(Some(0.95));
+    let emphasized = analyze_with(&signal[..], fft);
+
+    let plain_tilt = plain[high_bin] - plain[low_bin];
+    let emphasized_tilt = emphasized[high_bin] - emphasized[low_bin];
+    assert!(plain_tilt.abs() < 0.5);
+    assert!(emphasized_tilt > 20.0);
+}
+
+
+#[test]
+fn test_pre_emphasis_history_carries_over() {
+    let mut fft: AudioFft = AudioFft::new(2, 1);
+    fft.set_window_function(WindowFunction::Rectangular);
+    fft.set_pre_emphasis(Some(0.5));
+
+    fft.feed_data(&[10, 20]).unwrap();
+    fft.execute();
+    fft.feed_data(&[30, 40]).unwrap();
+    // The first sample of the second FFT is filtered against the last sample
+    // of the first one: 30 - 0.5 * 20 and 40 - 0.5 * 30
+    let input = fft.multichan_fft.get_inputs();
+    assert_eq!(input[0].to_vec(), vec![20.0, 25.0]);
+}
+
+
+#[test]
+fn test_dominant_frequency_between_bins() {
+    use fftw::synth::{sine, to_s16le};
+
+    let bin_width = 44100.0 / 2048.0;
+    let freq = 100.3 * bin_width;
+    let bytes = to_s16le(&sine(freq, 0.5, 44100.0, 2048)[..], 1);
+
+    for &output_scale in [OutputScale::Decibels, OutputScale::Magnitude, OutputScale::Power].iter() {
+        let mut fft: AudioFft = AudioFft::new(2048, 1);
+        fft.set_output_scale(output_scale);
+        fft.feed_u8_data(&bytes[..]);
+        fft.execute();
+        fft.compute_output();
+
+        // Picking the bin would be off by 0.3 bins
+        let estimate = fft.dominant_frequency(44100.0).unwrap();
+        assert!((estimate - freq).abs() < 0.05 * bin_width);
+    }
+}
+
+
+#[test]
+fn test_top_peaks_three_tones() {
+    use fftw::synth::{sine, mix, to_s16le};
+
+    let bin_width = 44100.0 / 4096.0;
+    let freqs = [40.2 * bin_width, 150.5 * bin_width, 400.7 * bin_width];
+    let a = sine(freqs[0], 0.4, 44100.0, 4096);
+    let b = sine(freqs[1], 0.2, 44100.0, 4096);
+    let c = sine(freqs[2], 0.1, 44100.0, 4096);
+    let bytes = to_s16le(&mix(&[&a[..], &b[..], &c[..]])[..], 1);
+
+    let mut fft: AudioFft = AudioFft::new(4096, 1);
+    fft.feed_u8_data(&bytes[..]);
+    fft.execute();
+    fft.compute_output();
+
+    let peaks = fft.top_peaks(3, 44100.0);
+    assert_eq!(peaks.len(), 3);
+    for (&(freq, _), &expected) in peaks.iter().zip(freqs.iter()) {
+        assert!((freq - expected).abs() < 0.1 * bin_width);
+    }
+    assert!(peaks[0].1 > peaks[1].1 && peaks[1].1 > peaks[2].1);
+}
+
+
+#[test]
+fn test_resize() {
+    use fftw::synth::{sine, to_s16le};
+
+    let mut fft: AudioFft = AudioFft::new(1024, 2);
+    fft.set_window_function(WindowFunction::FlatTop);
+    assert_eq!(fft.resize(1000), Err(FftError::InvalidFftSize(1000)));
+    assert_eq!(fft.get_fft_size(), 1024);
+    fft.resize(4096).unwrap();
+    assert_eq!(fft.get_fft_size(), 4096);
+    assert_eq!(fft.get_buf_size(), 4096 * 2 * 2);
+    assert_eq!(fft.get_window_function(), WindowFunction::FlatTop);
+
+    let bytes = to_s16le(&sine(1000.0, 0.5, 44100.0, 4096)[..], 2);
+    assert_eq!(bytes.len(), fft.get_buf_size());
+    fft.feed_u8_data(&bytes[..]);
+    fft.execute();
+    fft.compute_output();
+    assert_eq!(fft.get_output().len(), 4096/2 + 1);
+    assert_eq!(peak_bin(fft.get_output()), (1000.0 * 4096.0 / 44100.0f64).round() as usize);
+}
+
+
+#[test]
+fn test_resampling_keeps_frequency() {
+    use fftw::synth::{sine, to_s16le};
+
+    let bytes = to_s16le(&sine(1000.0, 0.5, 48000.0, 8192)[..], 1);
+    let mut fft: AudioFft = AudioFft::new(4096, 1);
+    fft.set_resampling(48000.0, 44100.0).unwrap();
+
+    // Feed in awkward chunks so the resampler carries over between them,
+    // until the FFT is full
+    let full = bytes.chunks(74).any(|chunk| fft.feed_u8_data(chunk) < chunk.len());
+    assert!(full);
+    fft.execute();
+    fft.compute_output();
+
+    let freq = fft.dominant_frequency(44100.0).unwrap();
+    assert!((freq - 1000.0).abs() < 1.0);
+}
+
+
+#[test]
+fn test_builder() {
+    let fft = AudioFftBuilder::<f32>::new()
+        .fft_size(2048)
+        .channels(1)
+        .window(WindowFunction::FlatTop)
+        .build()
+        .unwrap();
+    assert_eq!(fft.get_fft_size(), 2048);
+    assert_eq!(fft.get_channel_count(), 1);
+    assert_eq!(fft.get_buf_size(), 2048 * 4);
+    assert_eq!(fft.get_window_function(), WindowFunction::FlatTop);
+}
+
+
+#[test]
+fn test_builder_invalid() {
+    let result = AudioFftBuilder::<i16>::new().fft_size(1000).build();
+    assert_eq!(result.err(), Some(FftError::InvalidFftSize(1000)));
+
+    let result = AudioFftBuilder::<i16>::new().channels(0).build();
+    assert_eq!(result.err(), Some(FftError::NoChannels));
+
+    let result = AudioFftBuilder::<i16>::new()
+        .layout(ChannelLayout::Planar)
+        .resampling(48000.0, 44100.0)
+        .build();
+    assert_eq!(result.err(), Some(FftError::PlanarResampling));
+
+    let mut fft: AudioFft = AudioFft::new(1024, 2);
+    fft.set_channel_layout(ChannelLayout::Planar).unwrap();
+    assert_eq!(fft.set_resampling(48000.0, 44100.0), Err(FftError::PlanarResampling));
+
+    // The other way round is refused too
+    let mut fft: AudioFft = AudioFft::new(1024, 2);
+    fft.set_resampling(48000.0, 44100.0).unwrap();
+    assert_eq!(fft.set_channel_layout(ChannelLayout::Planar), Err(FftError::PlanarResampling));
+    assert_eq!(fft.set_channel_layout(ChannelLayout::Interleaved), Ok(()));
+
+    // A rate of 0 would never finish resampling
+    assert_eq!(fft.set_resampling(0.0, 44100.0), Err(FftError::InvalidSampleRate));
+    let result = AudioFftBuilder::<i16>::new()
+        .resampling(48000.0, ::std::f64::NAN)
+        .build();
+    assert_eq!(result.err(), Some(FftError::InvalidSampleRate));
+}
+
+
+#[test]
+fn test_discard_input_resets_resampler() {
+    use fftw::synth::{sine, to_s16le};
+
+    // Feeding half a window, discarding it and feeding the whole signal gives
+    // the same output as feeding the whole signal into a fresh FFT
+    let bytes = to_s16le(&sine(1000.0, 0.5, 48000.0, 8192)[..], 1);
+    let mut fresh: AudioFft = AudioFft::new(1024, 1);
+    fresh.set_resampling(48000.0, 44100.0).unwrap();
+    fresh.feed_u8_data(&bytes[..]);
+    fresh.execute();
+    fresh.compute_output();
+
+    let mut fft: AudioFft = AudioFft::new(1024, 1);
+    fft.set_resampling(48000.0, 44100.0).unwrap();
+    fft.feed_u8_data(&bytes[1000..2000]);
+    fft.discard_input();
+    fft.feed_u8_data(&bytes[..]);
+    fft.execute();
+    fft.compute_output();
+
+    assert_eq!(fft.get_output(), fresh.get_output());
+}
+
+
+#[test]
+fn test_amplitude_to_db() {
+    assert_eq!(amplitude_to_db(0.0, -120.0), -120.0);
+    assert_eq!(amplitude_to_db(1e-310, -120.0), -120.0);
+    assert_eq!(amplitude_to_db(1.0, -120.0), 0.0);
+    assert!((amplitude_to_db(1000.0, -120.0) - 60.0).abs() < 1e-9);
+    assert!((amplitude_to_db(0.5, -120.0) + 6.0206).abs() < 1e-4);
 }
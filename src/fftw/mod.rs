@@ -4,4 +4,9 @@ pub mod types;
 pub mod plan;
 pub mod multichannel;
 pub mod audio;
-pub mod hanning;
+pub mod window;
+pub mod octave;
+pub mod peaks;
+pub mod resample;
+pub mod sample;
+pub mod synth;
@@ -1,6 +1,13 @@
+use std::thread;
+
 use fftw::plan::*;
 
 
+/// The fewest channels worth spreading across threads. Below this, spawning
+/// the threads costs more than running the FFTs one after another.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 4;
+
+
 /// An FFTW Plan wrapper for multiple channels of data.
 pub struct MultiChannelFft {
     /// The size of the FFTs to be run
@@ -8,7 +15,10 @@ pub struct MultiChannelFft {
     /// The number of channels
     pub channel_count: usize,
     /// The plans for each channel
-    pub channel_plans: Vec<FftwPlan>
+    pub channel_plans: Vec<FftwPlan>,
+    /// The number of channels at which the FFTs are run on separate threads,
+    /// or None to always run them on the calling thread
+    parallel_threshold: Option<usize>
 }
 
 
@@ -26,6 +36,7 @@ impl MultiChannelFft {
             size: size,
             channel_count: channel_count,
             channel_plans: channel_plans,
+            parallel_threshold: Some(DEFAULT_PARALLEL_THRESHOLD)
         }
     }
 
@@ -39,13 +50,43 @@ impl MultiChannelFft {
         self.channel_plans.get_mut(index)
     }
 
-    /// Execute all of the FFT channels
+    /// Set the number of channels at which the FFTs are run on separate
+    /// threads. None keeps them on the calling thread.
+    pub fn set_parallel_threshold(&mut self, threshold: Option<usize>) {
+        self.parallel_threshold = threshold;
+    }
+
+    /// Get the number of channels at which the FFTs are run on separate
+    /// threads
+    pub fn get_parallel_threshold(&self) -> Option<usize> {
+        self.parallel_threshold
+    }
+
+    /// Execute all of the FFT channels, spreading them across threads if
+    /// there are enough of them
     pub fn execute(&mut self) {
+        match self.parallel_threshold {
+            Some(threshold) if self.channel_count >= threshold => self.execute_parallel(),
+            _ => self.execute_serial()
+        }
+    }
+
+    /// Execute the FFT channels one after another
+    pub fn execute_serial(&mut self) {
         for plan in self.channel_plans.iter_mut() {
             plan.execute();
         }
     }
 
+    /// Execute every FFT channel on its own thread
+    pub fn execute_parallel(&mut self) {
+        thread::scope(|scope| {
+            for plan in self.channel_plans.iter_mut() {
+                scope.spawn(move || plan.execute());
+            }
+        });
+    }
+
     /// Gets a vector of all of the input slices
     pub fn get_inputs<'a>(&'a mut self) -> Vec<&'a mut [f64]> {
         let mut inputs: Vec<&mut [f64]> = Vec::with_capacity(self.channel_count);
@@ -55,3 +96,30 @@ impl MultiChannelFft {
         inputs
     }
 }
+
+
+#[test]
+fn test_parallel_matches_serial() {
+    use fftw::synth::white_noise;
+
+    let mut fft = MultiChannelFft::new(512, 6);
+    let fill = |fft: &mut MultiChannelFft| {
+        for (seed, input) in fft.get_inputs().into_iter().enumerate() {
+            let noise = white_noise(1000.0, input.len(), seed as u64 + 1);
+            input.copy_from_slice(&noise[..]);
+        }
+    };
+    let outputs = |fft: &MultiChannelFft| -> Vec<Vec<(f64, f64)>> {
+        fft.channel_plans.iter()
+            .map(|plan| plan.get_output_slice().iter().map(|x| (x.re, x.im)).collect())
+            .collect()
+    };
+
+    fill(&mut fft);
+    fft.execute_serial();
+    let serial = outputs(&fft);
+
+    fill(&mut fft);
+    fft.execute_parallel();
+    assert_eq!(outputs(&fft), serial);
+}
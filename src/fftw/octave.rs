@@ -0,0 +1,98 @@
+/// The ISO 266 1/3-octave band numbers (relative to 1 kHz) covering the
+/// 25 Hz band through the 20 kHz band.
+const FIRST_BAND: i32 = -16;
+const LAST_BAND: i32 = 13;
+
+
+/// Get the exact (base 10) center frequency of a 1/3-octave band. Band 0 is
+/// centered on 1 kHz, and every 10 bands is a decade.
+pub fn band_center(band: i32) -> f64 {
+    1000.0 * 10f64.powf(band as f64 / 10.0)
+}
+
+
+/// Get the lower and upper edge frequencies of a 1/3-octave band. The edges
+/// sit half a band (a 20th of a decade) either side of the center.
+pub fn band_edges(band: i32) -> (f64, f64) {
+    let center = band_center(band);
+    let half_band = 10f64.powf(1.0 / 20.0);
+    (center / half_band, center * half_band)
+}
+
+
+/// Aggregates linear FFT magnitudes into the standard 1/3-octave bands from
+/// 25 Hz to 20 kHz by summing the power (magnitude squared) of the bins
+/// within each band's edges.
+///
+/// Arguments:
+///     input: Linear magnitudes, where input[k] is the bin at
+///            k * sample_rate / fft_size Hz
+///     fft_size: The size of the FFT that produced the input
+///     sample_rate: The sample rate of the audio in Hz
+/// Returns:
+///     A (center frequency, power) pair for each band. The power is None when
+///     no FFT bins fall inside the band, which happens for the lowest bands
+///     with small FFT sizes.
+pub fn third_octave_bands(input: &[f64], fft_size: usize, sample_rate: f64) -> Vec<(f64, Option<f64>)> {
+    let bin_width = sample_rate / fft_size as f64;
+    let mut bands: Vec<(f64, Option<f64>)> = Vec::with_capacity((LAST_BAND - FIRST_BAND + 1) as usize);
+
+    for band in FIRST_BAND..LAST_BAND + 1 {
+        let (low, high) = band_edges(band);
+        let mut power: Option<f64> = None;
+
+        for (k, &x) in input.iter().enumerate() {
+            let freq = k as f64 * bin_width;
+            if freq >= low && freq < high {
+                power = Some(power.unwrap_or(0.0) + x * x);
+            }
+        }
+
+        bands.push((band_center(band), power));
+    }
+
+    bands
+}
+
+
+#[test]
+fn test_band_edges() {
+    assert!((band_center(0) - 1000.0).abs() < 1e-9);
+    assert!((band_center(FIRST_BAND) - 25.12).abs() < 0.01);
+    assert!((band_center(LAST_BAND) - 19952.6).abs() < 0.1);
+
+    let (low, high) = band_edges(0);
+    assert!((low - 891.25).abs() < 0.01);
+    assert!((high - 1122.02).abs() < 0.01);
+
+    // Adjacent bands share an edge
+    assert!((band_edges(9).1 - band_edges(10).0).abs() < 1e-9);
+}
+
+
+#[test]
+fn test_third_octave_bands() {
+    // 1 Hz per bin, with energy only at 1 kHz
+    let mut input = vec![0.0; 24000];
+    input[1000] = 2.0;
+    let bands = third_octave_bands(&input[..], 48000, 48000.0);
+
+    assert_eq!(bands.len(), 30);
+    for &(center, power) in bands.iter() {
+        if (center - 1000.0).abs() < 1e-9 {
+            assert_eq!(power, Some(4.0));
+        } else {
+            assert_eq!(power, Some(0.0));
+        }
+    }
+}
+
+
+#[test]
+fn test_third_octave_bands_empty_low_bands() {
+    // ~21.5 Hz per bin leaves the lowest bands without any bins
+    let input = vec![1.0; 1024];
+    let bands = third_octave_bands(&input[..], 2048, 44100.0);
+    assert_eq!(bands[0].1, None);
+    assert!(bands[29].1.unwrap() > 0.0);
+}
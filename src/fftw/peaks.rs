@@ -0,0 +1,92 @@
+use std::cmp::Ordering;
+
+
+/// Estimates where a peak really is between FFT bins by fitting a parabola
+/// through the peak bin and its two neighbours. levels should be logarithmic
+/// (like decibels), which makes the fit much closer for windowed tones.
+///
+/// Returns the fractional bin index of the peak. A peak on the first or last
+/// bin, a flat peak, or one with non-finite neighbours isn't interpolated.
+pub fn interpolate_peak(levels: &[f64], index: usize) -> f64 {
+    if index == 0 || index + 1 >= levels.len() {
+        return index as f64;
+    }
+
+    let left = levels[index - 1];
+    let center = levels[index];
+    let right = levels[index + 1];
+    let denominator = left - 2.0 * center + right;
+    if !left.is_finite() || !center.is_finite() || !right.is_finite()
+        || denominator == 0.0 {
+        return index as f64;
+    }
+
+    let delta = 0.5 * (left - right) / denominator;
+    index as f64 + delta
+}
+
+
+/// Finds the loudest bin, ignoring the DC bin, and returns its interpolated
+/// fractional bin index. Returns None if there are no bins besides DC.
+pub fn dominant_bin(levels: &[f64]) -> Option<f64> {
+    if levels.len() < 2 {
+        return None;
+    }
+
+    let mut peak: usize = 1;
+    for (i, &x) in levels.iter().enumerate().skip(2) {
+        if x > levels[peak] {
+            peak = i;
+        }
+    }
+    Some(interpolate_peak(levels, peak))
+}
+
+
+/// Finds the local maxima, bins louder than both their neighbours, skipping
+/// the DC bin, though bin 1 is still compared against it. The shoulders of a
+/// peak are never reported on their own. The indexes are sorted loudest first.
+pub fn local_maxima(levels: &[f64]) -> Vec<usize> {
+    let mut maxima: Vec<usize> = (1..levels.len().saturating_sub(1))
+        .filter(|&i| levels[i] > levels[i - 1] && levels[i] > levels[i + 1])
+        .collect();
+    maxima.sort_by(|&a, &b| {
+        levels[b].partial_cmp(&levels[a]).unwrap_or(Ordering::Equal)
+    });
+    maxima
+}
+
+
+#[test]
+fn test_interpolate_peak() {
+    // A parabola with its vertex at 2.25
+    let levels: Vec<f64> = (0..5)
+        .map(|i| -(i as f64 - 2.25) * (i as f64 - 2.25))
+        .collect();
+    assert!((interpolate_peak(&levels[..], 2) - 2.25).abs() < 1e-12);
+
+    // Flat and edge peaks stay on their bin
+    assert_eq!(interpolate_peak(&[1.0, 1.0, 1.0], 1), 1.0);
+    assert_eq!(interpolate_peak(&[3.0, 1.0, 0.0], 0), 0.0);
+    assert_eq!(interpolate_peak(&[0.0, 1.0, 3.0], 2), 2.0);
+}
+
+
+#[test]
+fn test_dominant_bin() {
+    // DC is ignored even when it's the loudest
+    assert_eq!(dominant_bin(&[100.0, 1.0, 5.0, 1.0]), Some(2.0));
+    assert_eq!(dominant_bin(&[100.0]), None);
+}
+
+
+#[test]
+fn test_local_maxima() {
+    let levels = [9.0, 1.0, 3.0, 2.0, 5.0, 4.0, 6.0];
+    // DC and the last bin can't be local maxima
+    assert_eq!(local_maxima(&levels), vec![4, 2]);
+    assert_eq!(local_maxima(&[]), Vec::<usize>::new());
+
+    // The first bin past DC can be a peak
+    assert_eq!(local_maxima(&[0.0, 5.0, 1.0, 2.0, 1.0]), vec![1, 3]);
+}
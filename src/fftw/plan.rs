@@ -22,7 +22,9 @@ impl FftwPlan {
 
         let mut input = FftwAlignedArray::new(size);
         input.initialize(0.0);
-        let mut output = FftwAlignedArray::new(size);
+        // A real FFT only outputs the non-redundant half of the spectrum,
+        // from DC up to and including the Nyquist frequency
+        let mut output = FftwAlignedArray::new(size/2 + 1);
         output.initialize(FftwComplex{re: 0.0, im: 0.0});
 
         let plan = unsafe {
@@ -47,18 +49,30 @@ impl FftwPlan {
         unsafe { ext::fftw_execute(self.plan) };
     }
 
+    /// Get the size of the FFT
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
     /// Get a slice of the FFTW plan's input buffer
     pub fn get_input_slice<'a>(&'a mut self) -> &'a mut [f64] {
         self.input.as_mut_slice()
     }
 
-    /// Get a slice of the FFTW plan's output buffer
+    /// Get a slice of the FFTW plan's output buffer. A real FFT outputs
+    /// size/2 + 1 values: DC, the positive frequencies and the Nyquist
+    /// frequency.
     pub fn get_output_slice<'a>(&'a self) -> &'a [FftwComplex] {
-        // A real FFT outputs half of the input size.
-        &self.output.as_slice()[0..(self.size/2)]
+        self.output.as_slice()
     }
 }
 
+/// Each plan owns its own buffers and fftw_execute is thread safe, so a plan
+/// can be executed on another thread. Only planning and destroying plans
+/// isn't, and those never happen off the thread that owns the plan.
+unsafe impl Send for FftwPlan {}
+
+
 /// Unsafe because it has lifetimes.
 impl Drop for FftwPlan {
     /// Runds fftw_destroy plan when a plan goes out of scope
@@ -69,7 +83,7 @@ impl Drop for FftwPlan {
 
 
 /// Determine if a number is a power of two
-fn is_power_of_two(x: usize) -> bool {
+pub fn is_power_of_two(x: usize) -> bool {
     (x != 0) && (x != 1) && ((x & (x - 1)) == 0)
 }
 
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::mem;
+
+
+/// Converts interleaved audio from one sample rate to another with linear
+/// interpolation. Values are pushed in one at a time, and the position
+/// between input frames is carried over from one push to the next so the
+/// output doesn't drift no matter how the input is chunked.
+pub struct Resampler {
+    /// The number of input frames to move forward for each output frame
+    step: f64,
+    /// The number of interleaved channels
+    channel_count: usize,
+    /// How far past the previous input frame the next output frame is, in
+    /// input frames
+    position: f64,
+    /// The last whole input frame, or None before the first one arrives
+    previous: Option<Vec<f64>>,
+    /// The values of the input frame currently being pushed in
+    frame: Vec<f64>
+}
+
+
+impl Resampler {
+    /// Create a new Resampler which converts audio at input_rate into audio
+    /// at output_rate. Returns None unless both rates are finite and above 0.
+    pub fn new(input_rate: f64, output_rate: f64, channel_count: usize) -> Option<Resampler> {
+        if !is_valid_rate(input_rate) || !is_valid_rate(output_rate) {
+            return None;
+        }
+        Some(Resampler {
+            step: input_rate / output_rate,
+            channel_count: channel_count,
+            position: 0.0,
+            previous: None,
+            frame: Vec::with_capacity(channel_count)
+        })
+    }
+
+    /// Forget all of the audio pushed in so far
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.previous = None;
+        self.frame.clear();
+    }
+
+    /// Push in the next interleaved value. Whenever it completes an input
+    /// frame, any output frames which fall before it are added to output.
+    pub fn push(&mut self, value: f64, output: &mut VecDeque<f64>) {
+        self.frame.push(value);
+        if self.frame.len() < self.channel_count {
+            return;
+        }
+
+        match self.previous {
+            Some(ref mut previous) => {
+                while self.position < 1.0 {
+                    for (&a, &b) in previous.iter().zip(self.frame.iter()) {
+                        output.push_back(a + (b - a) * self.position);
+                    }
+                    self.position += self.step;
+                }
+                self.position -= 1.0;
+                // Reuse the old frame's buffer for the next one
+                mem::swap(previous, &mut self.frame);
+            },
+            None => {
+                let capacity = self.channel_count;
+                self.previous = Some(mem::replace(&mut self.frame, Vec::with_capacity(capacity)));
+            }
+        }
+        self.frame.clear();
+    }
+
+    /// Resample a buffer of interleaved values, carrying on from the last
+    /// push
+    pub fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        let mut output = VecDeque::new();
+        for &value in input.iter() {
+            self.push(value, &mut output);
+        }
+        output.into_iter().collect()
+    }
+}
+
+
+/// Check that a sample rate is finite and above 0
+fn is_valid_rate(rate: f64) -> bool {
+    rate.is_finite() && rate > 0.0
+}
+
+
+#[test]
+fn test_chunking_does_not_drift() {
+    use fftw::synth::sine;
+
+    let signal = sine(440.0, 1.0, 48000.0, 4800);
+    let whole = Resampler::new(48000.0, 44100.0, 1).unwrap().process(&signal[..]);
+
+    let mut resampler = Resampler::new(48000.0, 44100.0, 1).unwrap();
+    let mut chunked = Vec::new();
+    for chunk in signal.chunks(37) {
+        chunked.extend(resampler.process(chunk));
+    }
+
+    assert_eq!(chunked, whole);
+    // 4799 intervals of input, at 44100 / 48000 output frames per input frame
+    assert_eq!(whole.len(), (4799.0 * 44100.0 / 48000.0f64).ceil() as usize);
+}
+
+
+#[test]
+fn test_interleaved_channels() {
+    let mut resampler = Resampler::new(2.0, 4.0, 2).unwrap();
+    let output = resampler.process(&[0.0, 10.0, 1.0, 20.0, 2.0, 30.0]);
+    assert_eq!(output, vec![0.0, 10.0, 0.5, 15.0, 1.0, 20.0, 1.5, 25.0]);
+}
+
+
+#[test]
+fn test_invalid_rates() {
+    use std::f64::{INFINITY, NAN};
+
+    for &(input_rate, output_rate) in [(0.0, 44100.0), (48000.0, -1.0), (NAN, 44100.0), (48000.0, INFINITY)].iter() {
+        assert!(Resampler::new(input_rate, output_rate, 2).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+/// A type of audio sample that can be decoded from little endian bytes.
+///
+/// Every sample type converts to the same scale as a 16 bit sample, so a full
+/// scale u8, i16, i32 or f32 signal produces the same FFT output.
+pub trait Sample: Copy {
+    /// The number of bytes in one sample
+    const SIZE: usize;
+
+    /// Decode a sample from the first SIZE bytes of a little endian buffer
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+
+    /// Convert the sample to a float on the scale of a 16 bit sample
+    fn to_f64(self) -> f64;
+}
+
+
+impl Sample for u8 {
+    const SIZE: usize = 1;
+
+    fn from_le_bytes(bytes: &[u8]) -> u8 {
+        bytes[0]
+    }
+
+    /// Unsigned 8 bit samples are centered on 128
+    fn to_f64(self) -> f64 {
+        (self as f64 - 128.0) * 256.0
+    }
+}
+
+
+impl Sample for i16 {
+    const SIZE: usize = 2;
+
+    fn from_le_bytes(bytes: &[u8]) -> i16 {
+        (bytes[0] as u16 | (bytes[1] as u16) << 8) as i16
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+
+impl Sample for i32 {
+    const SIZE: usize = 4;
+
+    fn from_le_bytes(bytes: &[u8]) -> i32 {
+        (bytes[0] as u32
+            | (bytes[1] as u32) << 8
+            | (bytes[2] as u32) << 16
+            | (bytes[3] as u32) << 24) as i32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64 / 65536.0
+    }
+}
+
+
+impl Sample for f32 {
+    const SIZE: usize = 4;
+
+    fn from_le_bytes(bytes: &[u8]) -> f32 {
+        f32::from_bits(<i32 as Sample>::from_le_bytes(bytes) as u32)
+    }
+
+    /// Float samples are full scale at 1.0
+    fn to_f64(self) -> f64 {
+        self as f64 * 32768.0
+    }
+}
+
+
+#[test]
+fn test_decode_samples() {
+    assert_eq!(<u8 as Sample>::from_le_bytes(&[0xff]), 255);
+    assert_eq!(<u8 as Sample>::from_le_bytes(&[128]).to_f64(), 0.0);
+    assert_eq!(<u8 as Sample>::from_le_bytes(&[0]).to_f64(), -32768.0);
+
+    assert_eq!(<i16 as Sample>::from_le_bytes(&[0x34, 0x12]), 0x1234);
+    assert_eq!(<i16 as Sample>::from_le_bytes(&[0x00, 0x80]), -32768);
+    assert_eq!(<i16 as Sample>::from_le_bytes(&[0xff, 0x7f]).to_f64(), 32767.0);
+
+    assert_eq!(<i32 as Sample>::from_le_bytes(&[0x78, 0x56, 0x34, 0x12]), 0x12345678);
+    assert_eq!(<i32 as Sample>::from_le_bytes(&[0x00, 0x00, 0x00, 0x80]).to_f64(), -32768.0);
+
+    assert_eq!(<f32 as Sample>::from_le_bytes(&[0x00, 0x00, 0x00, 0x3f]), 0.5);
+    assert_eq!(<f32 as Sample>::from_le_bytes(&[0x00, 0x00, 0x80, 0xbf]).to_f64(), -32768.0);
+}
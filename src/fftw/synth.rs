@@ -0,0 +1,62 @@
+use std::f64::consts::PI;
+
+
+/// Generates a sine wave at freq Hz with the given peak amplitude, where full
+/// scale is 1.0
+pub fn sine(freq: f64, amplitude: f64, sample_rate: f64, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|i| amplitude * (2.0 * PI * freq * i as f64 / sample_rate).sin())
+        .collect()
+}
+
+
+/// Generates uniformly distributed white noise with the given peak amplitude.
+/// The same seed always gives the same noise.
+pub fn white_noise(amplitude: f64, len: usize, seed: u64) -> Vec<f64> {
+    // xorshift64 needs a non-zero state
+    let mut state: u64 = if seed == 0 { 0x2545F4914F6CDD1D } else { seed };
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let unit = (state >> 11) as f64 / (1u64 << 53) as f64;
+            amplitude * (unit * 2.0 - 1.0)
+        })
+        .collect()
+}
+
+
+/// Adds signals together sample by sample. The result is as long as the
+/// shortest signal.
+pub fn mix(signals: &[&[f64]]) -> Vec<f64> {
+    let len = signals.iter().map(|signal| signal.len()).min().unwrap_or(0);
+    (0..len)
+        .map(|i| signals.iter().map(|signal| signal[i]).sum())
+        .collect()
+}
+
+
+/// Encodes a signal as S16LE audio data, copying the signal into every one of
+/// channel_count interleaved channels. Samples outside of full scale are
+/// clipped.
+pub fn to_s16le(signal: &[f64], channel_count: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(signal.len() * channel_count * 2);
+    for &x in signal.iter() {
+        let clipped = x.clamp(-1.0, 1.0);
+        let sample = (clipped * 32767.0) as i16;
+        for _ in 0..channel_count {
+            bytes.push(sample as u8);
+            bytes.push((sample >> 8) as u8);
+        }
+    }
+    bytes
+}
+
+
+#[test]
+fn test_to_s16le() {
+    assert_eq!(to_s16le(&[0.0, 1.0, -1.0, 2.0], 1),
+               vec![0, 0, 0xff, 0x7f, 0x01, 0x80, 0xff, 0x7f]);
+    assert_eq!(to_s16le(&[1.0], 2), vec![0xff, 0x7f, 0xff, 0x7f]);
+}
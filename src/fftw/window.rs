@@ -0,0 +1,226 @@
+use std::f64::consts::PI;
+
+
+/// Coefficients of the 5-term flat top window
+const FLAT_TOP: [f64; 5] = [0.21557895, 0.41663158, 0.277263158, 0.083578947, 0.006947368];
+
+
+/// Coefficients of the 4-term Blackman-Harris window
+const BLACKMAN_HARRIS: [f64; 4] = [0.35875, 0.48829, 0.14128, 0.01168];
+
+
+/// Coefficients of the 4-term Nuttall window with a continuous first
+/// derivative
+const NUTTALL: [f64; 4] = [0.355768, 0.487396, 0.144232, 0.012604];
+
+
+/// The window functions that can be applied to audio before running the FFT
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum WindowFunction {
+    /// Good all-round leakage suppression. This is the default.
+    Hann,
+    /// No window at all. Every sample is multiplied by 1.
+    Rectangular,
+    /// A very wide main lobe, but measures the amplitude of tones accurately
+    /// no matter where they fall between bins
+    FlatTop,
+    /// Side lobes around 92 dB down, for picking out quiet tones right next
+    /// to loud ones. The main lobe is about twice as wide as Hann's.
+    BlackmanHarris,
+    /// Like Blackman-Harris, with side lobes that are a little higher at
+    /// first but fall away faster further from the tone
+    Nuttall,
+    /// A triangle rising linearly from 0 at the edges to 1 in the middle
+    Bartlett,
+    /// A parabola from 0 at the edges to 1 in the middle. It leaks less than
+    /// the rectangular window but more than Hann, with a narrower main lobe.
+    Welch,
+    /// A window with an adjustable beta parameter. Higher betas suppress side
+    /// lobes more at the cost of a wider main lobe. A beta of 0 is the same
+    /// as the rectangular window.
+    Kaiser(f64)
+}
+
+
+/// Computes a window made of a sum of cosines with alternating signs:
+/// a0 - a1*cos(phase) + a2*cos(2*phase) - a3*cos(3*phase) + ...
+fn cosine_sum(coefficients: &[f64], phase: f64) -> f64 {
+    coefficients.iter().enumerate().fold(0.0, |sum, (k, &a)| {
+        let term = a * (k as f64 * phase).cos();
+        if k % 2 == 0 { sum + term } else { sum - term }
+    })
+}
+
+
+/// Computes the zeroth order modified Bessel function of the first kind using
+/// its series expansion: the sum of ((x/2)^k / k!)^2 for k = 0, 1, 2...
+pub fn bessel_i0(x: f64) -> f64 {
+    let half_x = x / 2.0;
+    let mut sum: f64 = 1.0;
+    let mut term: f64 = 1.0;
+    let mut k: f64 = 1.0;
+
+    // The terms shrink quickly, so stop once they stop mattering
+    while term > sum * 1e-16 {
+        term *= (half_x / k) * (half_x / k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+
+impl WindowFunction {
+    /// Compute the multiplier for an index of a window that is size samples
+    /// long
+    pub fn coefficient(&self, index: usize, size: usize) -> f64 {
+        if size < 2 {
+            return 1.0;
+        }
+        let phase: f64 = 2.0 * PI * (index as f64) / ((size - 1) as f64);
+        // Where the index is in the window, from -1 at the start to 1 at the
+        // end
+        let ratio: f64 = 2.0 * (index as f64) / ((size - 1) as f64) - 1.0;
+
+        match *self {
+            WindowFunction::Hann => 0.5 * (1.0 - phase.cos()),
+            WindowFunction::Rectangular => 1.0,
+            WindowFunction::FlatTop => cosine_sum(&FLAT_TOP, phase),
+            WindowFunction::BlackmanHarris => cosine_sum(&BLACKMAN_HARRIS, phase),
+            WindowFunction::Nuttall => cosine_sum(&NUTTALL, phase),
+            WindowFunction::Bartlett => 1.0 - ratio.abs(),
+            WindowFunction::Welch => 1.0 - ratio * ratio,
+            WindowFunction::Kaiser(beta) => {
+                bessel_i0(beta * (1.0 - ratio * ratio).sqrt()) / bessel_i0(beta)
+            }
+        }
+    }
+}
+
+
+/// Precomputes the multipliers for a window function so computing the value
+/// only takes a single multiplication.
+pub struct WindowCalculator {
+    function: WindowFunction,
+    multipliers: Vec<f64>,
+    coherent_gain: f64
+}
+
+
+impl WindowCalculator {
+    /// The constructor computes the cache of window multiplier values
+    pub fn new(function: WindowFunction, fft_size: usize) -> WindowCalculator {
+        let multipliers: Vec<f64> = (0..fft_size)
+            .map(|i| function.coefficient(i, fft_size))
+            .collect();
+
+        let coherent_gain = if fft_size == 0 {
+            1.0
+        } else {
+            multipliers.iter().fold(0.0, |sum, &x| sum + x) / fft_size as f64
+        };
+
+        WindowCalculator {
+            function: function,
+            multipliers: multipliers,
+            coherent_gain: coherent_gain
+        }
+    }
+
+    /// Get the window function the multipliers were computed for
+    pub fn get_function(&self) -> WindowFunction {
+        self.function
+    }
+
+    /// Get the coherent gain of the window, which is the mean of its
+    /// multipliers. A tone's magnitude in the FFT output is scaled down by
+    /// this much by the window.
+    pub fn get_coherent_gain(&self) -> f64 {
+        self.coherent_gain
+    }
+
+    /// Multiplies the given value against the window multiplier value for
+    /// this index
+    pub fn get_value(&self, index: usize, val: f64) -> f64 {
+        self.multipliers[index] * val
+    }
+}
+
+
+#[test]
+fn test_window_coefficients() {
+    let hann = WindowCalculator::new(WindowFunction::Hann, 1024);
+    assert!(hann.get_value(0, 1.0).abs() < 1e-12);
+    assert!((hann.get_coherent_gain() - 0.5).abs() < 0.001);
+
+    let rect = WindowCalculator::new(WindowFunction::Rectangular, 1024);
+    assert_eq!(rect.get_value(0, 3.0), 3.0);
+    assert_eq!(rect.get_value(1023, 3.0), 3.0);
+    assert_eq!(rect.get_coherent_gain(), 1.0);
+
+    // The flat top window peaks at 1 in the middle
+    let flat_top = WindowCalculator::new(WindowFunction::FlatTop, 1025);
+    assert!((flat_top.get_value(512, 1.0) - 1.0).abs() < 1e-6);
+    assert!((flat_top.get_coherent_gain() - FLAT_TOP[0]).abs() < 0.001);
+}
+
+
+#[test]
+fn test_blackman_harris_and_nuttall_windows() {
+    for &(function, coefficients) in [(WindowFunction::BlackmanHarris, BLACKMAN_HARRIS),
+                                      (WindowFunction::Nuttall, NUTTALL)].iter() {
+        let window = WindowCalculator::new(function, 1025);
+        // Nearly 0 at the edges and 1 in the middle
+        assert!(window.get_value(0, 1.0) < 1e-3);
+        assert!((window.get_value(512, 1.0) - 1.0).abs() < 1e-3);
+        assert!((window.get_coherent_gain() - coefficients[0]).abs() < 0.001);
+    }
+}
+
+
+#[test]
+fn test_bartlett_and_welch_windows() {
+    // The triangle is 0 at the edges, 1 in the middle and linear in between
+    let bartlett = WindowCalculator::new(WindowFunction::Bartlett, 65);
+    assert!(bartlett.get_value(0, 1.0).abs() < 1e-12);
+    assert!(bartlett.get_value(64, 1.0).abs() < 1e-12);
+    assert!((bartlett.get_value(32, 1.0) - 1.0).abs() < 1e-12);
+    assert!((bartlett.get_value(16, 1.0) - 0.5).abs() < 1e-12);
+    assert!((bartlett.get_coherent_gain() - 0.5).abs() < 0.01);
+
+    let welch = WindowCalculator::new(WindowFunction::Welch, 64);
+    for i in 0..32 {
+        assert!((welch.get_value(i, 1.0) - welch.get_value(63 - i, 1.0)).abs() < 1e-12);
+    }
+    assert!(welch.get_value(0, 1.0).abs() < 1e-12);
+    assert!(welch.get_value(31, 1.0) > 0.99);
+    // A parabola averages to 2/3
+    assert!((welch.get_coherent_gain() - 2.0 / 3.0).abs() < 0.02);
+}
+
+
+#[test]
+fn test_bessel_i0() {
+    assert_eq!(bessel_i0(0.0), 1.0);
+    assert!((bessel_i0(1.0) - 1.2660658777520082).abs() < 1e-12);
+    assert!((bessel_i0(5.0) - 27.239871823604442).abs() < 1e-9);
+}
+
+
+#[test]
+fn test_kaiser_window() {
+    // A beta of 0 is the rectangular window
+    let kaiser = WindowCalculator::new(WindowFunction::Kaiser(0.0), 64);
+    for i in 0..64 {
+        assert!((kaiser.get_value(i, 1.0) - 1.0).abs() < 1e-12);
+    }
+
+    let kaiser = WindowCalculator::new(WindowFunction::Kaiser(8.6), 64);
+    for i in 0..32 {
+        assert!((kaiser.get_value(i, 1.0) - kaiser.get_value(63 - i, 1.0)).abs() < 1e-12);
+    }
+    assert!(kaiser.get_value(0, 1.0) < 0.002);
+    assert!(kaiser.get_value(31, 1.0) > 0.99);
+}
@@ -0,0 +1,87 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+/// Holds the animation to a target frame rate by sleeping away whatever is
+/// left of each frame's time budget, so a fast machine doesn't spend a whole
+/// CPU core drawing frames nobody can see.
+pub struct FrameLimiter {
+    /// The time each frame should take, or None if there is no cap
+    frame_budget: Option<Duration>,
+    /// When the last frame started
+    last_frame: Option<Instant>
+}
+
+
+impl FrameLimiter {
+    /// Create a new FrameLimiter. None means frames are never delayed.
+    pub fn new(target_fps: Option<u32>) -> FrameLimiter {
+        let mut limiter = FrameLimiter {
+            frame_budget: None,
+            last_frame: None
+        };
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    /// Set the frame rate to hold the animation to. None disables the cap.
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_budget = match target_fps {
+            Some(fps) if fps > 0 => Some(Duration::from_secs(1) / fps),
+            _ => None
+        };
+    }
+
+    /// Get how long to wait at the given time before the next frame may
+    /// start
+    pub fn time_to_wait(&self, now: Instant) -> Duration {
+        match (self.frame_budget, self.last_frame) {
+            (Some(budget), Some(last_frame)) => {
+                let elapsed = now.duration_since(last_frame);
+                budget.checked_sub(elapsed).unwrap_or(Duration::new(0, 0))
+            },
+            _ => Duration::new(0, 0)
+        }
+    }
+
+    /// Sleep for the rest of the current frame's budget, then start timing
+    /// the next frame. Call this once before drawing each frame.
+    pub fn wait(&mut self) {
+        let wait = self.time_to_wait(Instant::now());
+        if wait > Duration::new(0, 0) {
+            thread::sleep(wait);
+        }
+        self.last_frame = Some(Instant::now());
+    }
+}
+
+
+#[test]
+fn test_time_to_wait() {
+    let start = Instant::now();
+    let mut limiter = FrameLimiter::new(Some(10));
+
+    // The first frame never waits
+    assert_eq!(limiter.time_to_wait(start), Duration::new(0, 0));
+
+    limiter.last_frame = Some(start);
+    assert_eq!(limiter.time_to_wait(start + Duration::from_millis(30)), Duration::from_millis(70));
+    assert_eq!(limiter.time_to_wait(start + Duration::from_millis(150)), Duration::new(0, 0));
+
+    limiter.set_target_fps(None);
+    assert_eq!(limiter.time_to_wait(start), Duration::new(0, 0));
+}
+
+
+#[test]
+fn test_wait_holds_frame_rate() {
+    let mut limiter = FrameLimiter::new(Some(20));
+    let start = Instant::now();
+    for _ in 0..4 {
+        limiter.wait();
+    }
+    // Three full frames of 50ms after the first one
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(150));
+    assert!(elapsed < Duration::from_millis(1000));
+}
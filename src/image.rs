@@ -0,0 +1,67 @@
+use std::io::{self, Write};
+use color::Rgb;
+
+
+/// An RGB image that frames of the visualizer can be drawn into, for saving
+/// screenshots or thumbnails without a terminal.
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>
+}
+
+
+impl Image {
+    /// Create a new all black image
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width: width,
+            height: height,
+            pixels: vec![(0, 0, 0); width * height]
+        }
+    }
+
+    /// Get the width of the image in pixels
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image in pixels
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the color of a pixel. y is counted from the top of the image.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Set the color of a pixel. y is counted from the top of the image.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Write the image out in the binary PPM (P6) format, which nearly every
+    /// image viewer and converter can read.
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        try!(write!(out, "P6\n{} {}\n255\n", self.width, self.height));
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.pixels.len() * 3);
+        for &(r, g, b) in self.pixels.iter() {
+            bytes.push(r);
+            bytes.push(g);
+            bytes.push(b);
+        }
+        out.write_all(&bytes[..])
+    }
+}
+
+
+#[test]
+fn test_write_ppm() {
+    let mut image = Image::new(2, 1);
+    image.set_pixel(1, 0, (1, 2, 3));
+
+    let mut out: Vec<u8> = Vec::new();
+    image.write_ppm(&mut out).unwrap();
+    assert_eq!(out, b"P6\n2 1\n255\n\x00\x00\x00\x01\x02\x03".to_vec());
+}
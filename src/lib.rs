@@ -1,5 +1,16 @@
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+
 pub mod ncurses;
 pub mod pulse;
 pub mod visualizer;
 pub mod fftw;
 pub mod viz_runner;
+pub mod color;
+pub mod image;
+pub mod frame_limiter;
+pub mod controls;
+pub mod capture;
+pub mod config;
+pub mod envelope;
@@ -4,9 +4,10 @@ extern crate rusty_bars;
 use rusty_bars::pulse::PulseAudioMainloop;
 use rusty_bars::viz_runner::VizRunner;
 
-/// Start the visualizer for your default PulseAudio output.
+/// Start the visualizer for your default PulseAudio output, rendering on its
+/// own thread.
 fn main() {
     let mainloop = PulseAudioMainloop::new();
-    VizRunner::new(&mainloop);
+    VizRunner::new_threaded(&mainloop, Some(60));
     mainloop.run();
 }
@@ -1,5 +1,5 @@
 extern crate libc;
-use self::libc::{c_int, c_char};
+use self::libc::{c_int, c_char, c_uchar, c_short, c_void};
 
 /// Module for external ncurses functions and types
 
@@ -12,9 +12,19 @@ extern {
     pub fn initscr() -> *mut Window;
     pub fn endwin() -> c_int;
     pub fn wrefresh(win: *mut Window) -> c_int;
+    pub fn wnoutrefresh(win: *mut Window) -> c_int;
+    pub fn doupdate() -> c_int;
     pub fn mvwaddstr(win: *mut Window, y: c_int, x: c_int, text: *const c_char) -> c_int;
     pub fn mvwaddnstr(win: *mut Window, y: c_int, x: c_int, text: *const c_char, n: c_int) -> c_int;
     pub fn getmaxy(win: *mut Window) -> c_int;
     pub fn getmaxx(win: *mut Window) -> c_int;
     pub fn curs_set(visibility: c_int) -> c_int;
+    pub fn wgetch(win: *mut Window) -> c_int;
+    pub fn nodelay(win: *mut Window, bf: c_uchar) -> c_int;
+    pub fn keypad(win: *mut Window, bf: c_uchar) -> c_int;
+    pub fn has_colors() -> c_uchar;
+    pub fn start_color() -> c_int;
+    pub fn use_default_colors() -> c_int;
+    pub fn init_pair(pair: c_short, f: c_short, b: c_short) -> c_int;
+    pub fn wcolor_set(win: *mut Window, pair: c_short, opts: *mut c_void) -> c_int;
 }
@@ -2,10 +2,32 @@
 extern crate libc;
 
 use std::ffi::CString;
-use self::libc::{c_int, c_char};
+use std::ptr;
+use self::libc::{c_int, c_char, c_uchar, c_short};
 use ncurses::ext;
 
 
+/// The key code getch returns when the terminal has been resized. By the time
+/// it is returned, ncurses has already resized its windows to match.
+pub const KEY_RESIZE: c_int = 0o632;
+
+
+/// The colors every color terminal has
+pub const COLOR_BLACK: c_short = 0;
+pub const COLOR_RED: c_short = 1;
+pub const COLOR_GREEN: c_short = 2;
+pub const COLOR_YELLOW: c_short = 3;
+pub const COLOR_BLUE: c_short = 4;
+pub const COLOR_MAGENTA: c_short = 5;
+pub const COLOR_CYAN: c_short = 6;
+pub const COLOR_WHITE: c_short = 7;
+
+
+/// The color number that means the terminal's own default color. Only valid
+/// after use_default_colors has succeeded.
+pub const COLOR_DEFAULT: c_short = -1;
+
+
 /// Safe wrapper for the ncurses endwin function. Call this when you are done
 /// with ncurses.
 pub fn endwin() -> Result<(), c_int> {
@@ -51,7 +73,7 @@ impl Window {
     }
 
     /// Add a raw array of c_char to the window
-    pub fn addbytes(&mut self, y: c_int, x: c_int, text: &Vec<c_char>) -> Result<c_int, c_int> {
+    pub fn addbytes(&mut self, y: c_int, x: c_int, text: &[c_char]) -> Result<c_int, c_int> {
         handle_err(unsafe{
             ext::mvwaddnstr(self.w, y, x, text.as_ptr(), text.len() as c_int)
         })
@@ -62,6 +84,17 @@ impl Window {
         handle_err(unsafe{ ext::wrefresh(self.w) })
     }
 
+    /// Copy the window to ncurses' virtual screen without updating the
+    /// display. Call doupdate to put the virtual screen on the display.
+    pub fn noutrefresh(&mut self) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::wnoutrefresh(self.w) })
+    }
+
+    /// Update the display to match ncurses' virtual screen in one go
+    pub fn doupdate(&mut self) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::doupdate() })
+    }
+
     /// Get the maximum y on the screen
     pub fn get_max_y(&self) -> Result<c_int, c_int> {
         handle_err(unsafe{ ext::getmaxy(self.w) })
@@ -81,6 +114,110 @@ impl Window {
     pub fn curs_set(&mut self, visibility: c_int) -> Result<c_int, c_int> {
         handle_err(unsafe{ ext::curs_set(visibility) })
     }
+
+    /// Read a key. Returns None if no key is available and getch is set to
+    /// not block with nodelay.
+    pub fn getch(&mut self) -> Option<c_int> {
+        handle_err(unsafe{ ext::wgetch(self.w) }).ok()
+    }
+
+    /// Set whether getch returns immediately when no key is available
+    pub fn nodelay(&mut self, enabled: bool) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::nodelay(self.w, enabled as c_uchar) })
+    }
+
+    /// Set whether getch decodes function keys (and resize events) into
+    /// single key codes
+    pub fn keypad(&mut self, enabled: bool) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::keypad(self.w, enabled as c_uchar) })
+    }
+
+    /// Whether the terminal can show colors
+    pub fn has_colors(&self) -> bool {
+        unsafe{ ext::has_colors() != 0 }
+    }
+
+    /// Set up ncurses to draw in color
+    pub fn start_color(&mut self) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::start_color() })
+    }
+
+    /// Allow COLOR_DEFAULT to be used for the terminal's own colors
+    pub fn use_default_colors(&mut self) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::use_default_colors() })
+    }
+
+    /// Define a color pair of a foreground and background color. Pair 0 is
+    /// the terminal's default and can't be changed.
+    pub fn init_pair(&mut self, pair: c_short, foreground: c_short, background: c_short) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::init_pair(pair, foreground, background) })
+    }
+
+    /// Draw everything added after this in the given color pair
+    pub fn color_set(&mut self, pair: c_short) -> Result<c_int, c_int> {
+        handle_err(unsafe{ ext::wcolor_set(self.w, pair, ptr::null_mut()) })
+    }
+}
+
+/// The drawing operations needed to render onto a grid of characters. Window
+/// implements this for a real terminal, but anything that can stand in for a
+/// terminal can implement it too.
+///
+/// Drawing is double buffered: everything drawn for a frame goes into a
+/// buffer, noutrefresh marks the buffer as ready, and doupdate puts it on the
+/// display all at once so a half drawn frame is never shown.
+pub trait Screen {
+    /// Add a string to the screen starting at the given location
+    fn addstr(&mut self, y: c_int, x: c_int, text: &str) -> Result<c_int, c_int>;
+
+    /// Add a raw array of c_char to the screen starting at the given location
+    fn addbytes(&mut self, y: c_int, x: c_int, text: &[c_char]) -> Result<c_int, c_int>;
+
+    /// Mark everything drawn so far as ready without updating the display
+    fn noutrefresh(&mut self) -> Result<c_int, c_int>;
+
+    /// Update the display with everything marked as ready
+    fn doupdate(&mut self) -> Result<c_int, c_int>;
+
+    /// Get a tuple containing the maximum y and x on the screen
+    fn get_max_yx(&self) -> Result<(c_int, c_int), c_int>;
+
+    /// Read a key without blocking. Returns None if no key is available.
+    fn getch(&mut self) -> Option<c_int>;
+
+    /// Draw everything added after this in the given color pair
+    fn color_set(&mut self, pair: c_short) -> Result<c_int, c_int>;
+}
+
+
+impl Screen for Window {
+    fn addstr(&mut self, y: c_int, x: c_int, text: &str) -> Result<c_int, c_int> {
+        Window::addstr(self, y, x, text)
+    }
+
+    fn addbytes(&mut self, y: c_int, x: c_int, text: &[c_char]) -> Result<c_int, c_int> {
+        Window::addbytes(self, y, x, text)
+    }
+
+    fn noutrefresh(&mut self) -> Result<c_int, c_int> {
+        Window::noutrefresh(self)
+    }
+
+    fn doupdate(&mut self) -> Result<c_int, c_int> {
+        Window::doupdate(self)
+    }
+
+    fn get_max_yx(&self) -> Result<(c_int, c_int), c_int> {
+        Window::get_max_yx(self)
+    }
+
+    fn getch(&mut self) -> Option<c_int> {
+        Window::getch(self)
+    }
+
+    fn color_set(&mut self, pair: c_short) -> Result<c_int, c_int> {
+        Window::color_set(self, pair)
+    }
 }
 
 impl Drop for Window {
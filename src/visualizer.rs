@@ -1,62 +1,549 @@
 extern crate libc;
 
-use self::libc::{c_int, c_char};
-use ncurses::window::Window;
+use std::collections::VecDeque;
+use std::mem;
+use self::libc::{c_int, c_char, c_short};
+use color::{self, ColorScheme};
+use envelope::Envelope;
+use fftw::audio::{amplitude_to_db, DEFAULT_DB_FLOOR};
+use image::Image;
+use ncurses::window::{Screen, Window, KEY_RESIZE, COLOR_BLACK, COLOR_DEFAULT};
+
+
+/// How far in dB the loudest level has to rise above the silence threshold to
+/// wake the display up again
+const SILENCE_HYSTERESIS_DB: f64 = 3.0;
+
+
+/// The sample rate assumed for the spectra unless told otherwise
+const DEFAULT_SAMPLE_RATE: f64 = 44100.0;
 
 
 /// The character to use for a bar
 const BAR_CHAR: c_char = '|' as c_char;
 
 
+/// The character to mark the recent peak of a bar with
+const PEAK_CHAR: c_char = '-' as c_char;
+
+
+/// The character to draw at the tip of a bar that has clipped
+const CLIP_CHAR: c_char = '!' as c_char;
+
+
 /// The character to use for rows above the bar
 const EMPTY_CHAR: c_char = ' ' as c_char;
 
 
-/// The character to use where there is a lack of data due to scaling issues.
+/// The default character to use where there is a lack of data due to scaling
+/// issues, and for the divider between channels.
 /// (If the user sees this character, it is because the visualizer wasn't
 /// properly scaled to the window width)
 const BORDER_CHAR: c_char = ' ' as c_char;
 
 
+/// The characters a spectrogram shades its cells with, from silent to loudest
+const SHADE_CHARS: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+
 /// The character to initialize the row arrays with.
 /// (This is not the same as EMPTY_CHAR so that it is easy to detect that we
-/// didn't draw some part of the screen. Users should never see this.)
+/// didn't draw some part of the screen. Users should never see this unless
+/// they ask to see the gaps.)
 const INIT_CHAR: c_char = '#' as c_char;
 
 
-/// Scales down a vector by averaging the elements between the resulting points
-pub fn scale_fft_output(input: &[f64], new_len: usize) -> Vec<f64> {
+/// How the FFT bins that fall into one band are combined into a single value
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BinReduce {
+    /// The mean of the bins. This is the default.
+    Average,
+    /// The loudest bin, which keeps narrow peaks like single tones visible
+    Max,
+    /// The total of the bins, which preserves the energy in each band
+    Sum,
+    /// The total power of bins holding decibel levels, back in decibels. A
+    /// band's height then tracks how much energy it carries, so the total
+    /// lit area follows the overall loudness. The tradeoff is that wide bands
+    /// gather more bins than narrow ones, so with a logarithmic band layout
+    /// the bass bands stand taller than they would sound.
+    Energy
+}
+
+
+impl BinReduce {
+    /// Combine a band's bins into one value
+    fn reduce(&self, bins: &[f64]) -> f64 {
+        match *self {
+            BinReduce::Average => bins.iter().fold(0.0, |sum, &x| sum + x) / bins.len() as f64,
+            BinReduce::Max => bins.iter().fold(bins[0], |max, &x| if x > max { x } else { max }),
+            BinReduce::Sum => bins.iter().fold(0.0, |sum, &x| sum + x),
+            BinReduce::Energy => {
+                let power = bins.iter().fold(0.0, |sum, &x| sum + 10.0f64.powf(x / 10.0));
+                // The total is never quieter than the quietest bin
+                let floor = bins.iter().fold(bins[0], |min, &x| if x < min { x } else { min });
+                amplitude_to_db(power.sqrt(), floor)
+            }
+        }
+    }
+}
+
+
+/// Scales down a vector to new_len bands by combining the elements that fall
+/// into each band. Every element falls into exactly one band, and the bands
+/// differ in size by at most one element.
+pub fn scale_fft_output(input: &[f64], new_len: usize, reduce: BinReduce) -> Vec<f64> {
     if new_len >= input.len() {
         return input.to_vec();
     }
 
-    let band_size: usize = input.len() / new_len;
-    assert!(band_size > 0);
-    let mut output: Vec<f64> = Vec::with_capacity(new_len);
+    (0..new_len)
+        .map(|i| {
+            let start = i * input.len() / new_len;
+            let end = (i + 1) * input.len() / new_len;
+            reduce.reduce(&input[start..end])
+        })
+        .collect()
+}
+
+
+/// How the FFT bins are spread across the bands
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BandSpacing {
+    /// Every band covers the same number of bins. This is the default.
+    Linear,
+    /// Each band covers the same ratio of frequencies, like the keys of a
+    /// piano, so the bass gets as much of the screen as the treble
+    Logarithmic
+}
+
+
+/// Scales down a spectrum to new_len bands spaced logarithmically from bin
+/// low_bin up to the last bin, by combining the bins in each band. Bins below
+/// low_bin are left out, as is DC, which has no place on a log scale.
+///
+/// With few bins the low bands are narrower than a bin, so some of them hold
+/// no bins at all. If fill_empty is set, those bands borrow the bin their
+/// centre frequency falls in so every band has data. Otherwise they are 0.
+pub fn scale_fft_output_log(input: &[f64], low_bin: usize, new_len: usize, reduce: BinReduce, fill_empty: bool) -> Vec<f64> {
+    let low = ::std::cmp::max(low_bin, 1);
+    if new_len == 0 || low >= input.len() {
+        return vec![0.0; new_len];
+    }
+
+    // The fractional bin each band starts at
+    let ratio = input.len() as f64 / low as f64;
+    let edges: Vec<f64> = (0..new_len + 1)
+        .map(|i| low as f64 * ratio.powf(i as f64 / new_len as f64))
+        .collect();
+
+    edges.windows(2).map(|edge| {
+        let start = ::std::cmp::min(edge[0].floor() as usize, input.len());
+        let end = ::std::cmp::min(edge[1].floor() as usize, input.len());
+        if start < end {
+            reduce.reduce(&input[start..end])
+        } else if fill_empty {
+            let centre = (edge[0] * edge[1]).sqrt().floor() as usize;
+            input[::std::cmp::min(centre, input.len() - 1)]
+        } else {
+            0.0
+        }
+    }).collect()
+}
+
 
-    let mut temp_count: usize = 0;
-    let mut sum: f64 = 0.0;
+/// Errors in a list of band edges given to scale_fft_output_custom
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BandEdgeError {
+    /// At least two edges are needed to make a band
+    TooFewEdges(usize),
+    /// The edge at this index isn't higher than the one before it
+    Unsorted(usize),
+    /// This edge is negative or above the Nyquist frequency
+    OutOfRange(f64)
+}
+
+
+/// Combines the FFT bins of input into bands with the given edge frequencies
+/// in Hz, so n edges give n - 1 bands. The bins are taken to run evenly from
+/// 0 Hz to the Nyquist frequency, as AudioFft gives them. A bin belongs to the
+/// band its frequency falls in, counting each band's lower edge but not its
+/// upper one, except that the last band includes its upper edge.
+///
+/// A band too narrow to hold any bins gets the spectrum interpolated at its
+/// centre frequency instead, so it follows its neighbours rather than
+/// dropping to zero.
+pub fn scale_fft_output_custom(input: &[f64], edges: &[f64], sample_rate: f64, reduce: BinReduce) -> Result<Vec<f64>, BandEdgeError> {
+    try!(check_band_edges(edges, sample_rate));
+    let nyquist = sample_rate / 2.0;
+    if input.len() < 2 {
+        return Ok(vec![input.first().cloned().unwrap_or(0.0); edges.len() - 1]);
+    }
 
-    for &x in input.iter() {
-        if temp_count >= band_size {
-            let avg: f64 = sum/temp_count as f64;
-            output.push(avg);
-            temp_count = 0;
-            sum = 0.0;
+    let bin_width = nyquist / (input.len() - 1) as f64;
+    let last_band = edges.len() - 2;
+    Ok(edges.windows(2).enumerate().map(|(band, edge)| {
+        let start = (edge[0] / bin_width).ceil() as usize;
+        let end = if band == last_band {
+            (edge[1] / bin_width).floor() as usize + 1
         } else {
-            sum += x;
-            temp_count+=1;
+            (edge[1] / bin_width).ceil() as usize
+        };
+        let end = ::std::cmp::min(end, input.len());
+
+        if start < end {
+            reduce.reduce(&input[start..end])
+        } else {
+            interpolate_bins(input, (edge[0] + edge[1]) / 2.0 / bin_width)
+        }
+    }).collect())
+}
+
+
+/// Check that edges make at least one band, rising from 0 Hz or above to the
+/// Nyquist frequency of sample_rate or below
+fn check_band_edges(edges: &[f64], sample_rate: f64) -> Result<(), BandEdgeError> {
+    if edges.len() < 2 {
+        return Err(BandEdgeError::TooFewEdges(edges.len()));
+    }
+    let nyquist = sample_rate / 2.0;
+    for (i, &edge) in edges.iter().enumerate() {
+        if !(edge >= 0.0 && edge <= nyquist) {
+            return Err(BandEdgeError::OutOfRange(edge));
+        }
+        if i > 0 && edge <= edges[i - 1] {
+            return Err(BandEdgeError::Unsorted(i));
+        }
+    }
+    Ok(())
+}
+
+
+/// Get the [start, end) range of bins with frequencies from low to high Hz in
+/// a spectrum of len bins running from 0 Hz to the Nyquist frequency. The
+/// range always holds at least one bin so there is something to draw.
+fn freq_range_bins(len: usize, low: f64, high: f64, sample_rate: f64) -> (usize, usize) {
+    if len < 2 {
+        return (0, len);
+    }
+    let bin_width = sample_rate / 2.0 / (len - 1) as f64;
+    let start = ::std::cmp::min((low.max(0.0) / bin_width).ceil() as usize, len - 1);
+    let end = ::std::cmp::min((high.max(0.0) / bin_width).floor() as usize + 1, len);
+    (start, ::std::cmp::max(end, start + 1))
+}
+
+
+/// Get the value of input at a fractional bin position by linear
+/// interpolation between the bins either side of it
+fn interpolate_bins(input: &[f64], position: f64) -> f64 {
+    let below = ::std::cmp::min(position.floor() as usize, input.len() - 1);
+    let above = ::std::cmp::min(below + 1, input.len() - 1);
+    let frac = position - below as f64;
+    input[below] + (input[above] - input[below]) * frac
+}
+
+
+/// How the visualizer arranges spectra on the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Layout {
+    /// A single spectrum with bars standing on the bottom of the screen
+    Bars,
+    /// The left channel's spectrum on the left half of the screen and the
+    /// right channel's spectrum on the right half, split by a divider column
+    StereoSplit,
+    /// A single spectrum with bars growing both up and down from the middle
+    /// row of the screen
+    Mirror,
+    /// A single spectrum with bars hanging down from the top of the screen
+    TopDown,
+    /// A single spectrum drawn twice, mirrored left to right, with the bass
+    /// in the middle of the screen and the treble out at both edges
+    MonoMirror
+}
+
+
+/// Errors that can occur while rendering a frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderError {
+    /// An ncurses call failed with the given return code
+    Curses(c_int),
+    /// The number of channels given doesn't match what the layout needs
+    ChannelCount(usize),
+    /// The custom band edges don't fit the sample rate
+    BandEdges(BandEdgeError)
+}
+
+
+/// What gets drawn in a single column of the window
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Column {
+    /// A bar of the given height
+    Bar(usize),
+    /// Empty space between bands
+    Gap,
+    /// A column with no data because of a scaling mismatch
+    Border
+}
+
+
+/// Get the number of one column bars that fit in a window that is width
+/// columns wide with gap empty columns between each bar. A gap too large to
+/// fit two bars is clamped so that a single bar is still drawn.
+fn bar_count(width: usize, gap: usize) -> usize {
+    (width + gap) / (gap + 1)
+}
+
+
+/// Spreads band_count bands across a window that is width columns wide,
+/// leaving gap columns of spacing between neighbouring bands when there is
+/// room for it. Returns the [start, end) column range of each band. The first
+/// band always starts at column 0 and the last band always ends at width, so
+/// no columns are left over at the edges.
+fn band_column_ranges(band_count: usize, width: usize, gap: usize) -> Vec<(usize, usize)> {
+    let band_count = if band_count > width { width } else { band_count };
+    if band_count == 0 {
+        return Vec::new();
+    }
+
+    // Only leave gaps if every band can still be at least one column wide
+    let gap: usize = if bar_count(width, gap) >= band_count { gap } else { 0 };
+    let drawable = width - gap * (band_count - 1);
+
+    (0..band_count)
+        .map(|i| {
+            let start = i * drawable / band_count + i * gap;
+            let end = (i + 1) * drawable / band_count + i * gap;
+            (start, end)
+        })
+        .collect()
+}
+
+
+/// Lays out the bar heights across a window that is width columns wide. When
+/// spread is set, each bar is drawn as a block of columns with spacing
+/// between them. Otherwise each bar gets one column and any columns without
+/// data are borders.
+fn layout_columns(heights: &[usize], width: usize, spread: bool, gap: usize) -> Vec<Column> {
+    if !spread {
+        return (0..width)
+            .map(|x| if x < heights.len() { Column::Bar(heights[x]) } else { Column::Border })
+            .collect();
+    }
+
+    let mut columns: Vec<Column> = vec![Column::Gap; width];
+    for (&height, (start, end)) in heights.iter().zip(band_column_ranges(heights.len(), width, gap)) {
+        for column in columns[start..end].iter_mut() {
+            *column = Column::Bar(height);
         }
     }
+    columns
+}
+
+
+/// Split a window that is width columns wide into the widths of the left and
+/// right halves of the stereo layout. One column in the middle is used for the
+/// divider and the left side gets any odd column left over.
+fn stereo_widths(width: usize) -> (usize, usize) {
+    let available = if width > 0 { width - 1 } else { 0 };
+    (available - available / 2, available / 2)
+}
+
+
+/// Clamp a fractional row to the rows of a window that is height rows tall
+fn clamp_level(row: f64, height: usize) -> f64 {
+    let max_row = (height - 1) as f64;
+    if row > max_row {
+        max_row
+    } else if row > 0.0 {
+        row
+    } else {
+        0.0
+    }
+}
+
+
+/// Get the fractional row the top of a bar reaches for the level x in dB when
+/// the window shows a fixed range of levels. The floor maps to the bottom row
+/// and the ceiling to the top row; anything outside the range is clamped to
+/// it.
+fn db_bar_level(x: f64, floor: f64, ceiling: f64, height: usize) -> f64 {
+    if height > 0 && ceiling > floor && x > floor {
+        clamp_level((x - floor) / (ceiling - floor) * (height - 1) as f64, height)
+    } else {
+        0.0
+    }
+}
+
+
+/// Get the fractional row the top of a bar reaches for the linear magnitude x
+/// on a logarithmic scale from floor at the bottom row to ceiling at the top
+/// row. Each row covers the same ratio of magnitudes, like dB do, without
+/// needing the output in dB. Magnitudes at or below the floor are on the
+/// bottom row.
+fn log_bar_level(x: f64, floor: f64, ceiling: f64, height: usize) -> f64 {
+    if height > 0 && floor > 0.0 && ceiling > floor && x > floor {
+        let fraction = (x.ln() - floor.ln()) / (ceiling.ln() - floor.ln());
+        clamp_level(fraction * (height - 1) as f64, height)
+    } else {
+        0.0
+    }
+}
+
+
+/// Get the fractional row the top of a bar reaches for the value x, given the
+/// largest value in the frame and the height of the window. It is always
+/// within [0, height - 1].
+fn bar_level(x: f64, max_val: f64, height: usize) -> f64 {
+    if x < 1.0 || height == 0 {
+        return 0.0;
+    }
+    clamp_level((x / max_val) * (height - 1) as f64, height)
+}
+
+
+/// Round a bar's fractional level to a row, but keep it at the row it was
+/// drawn at last frame unless the level has moved more than deadband rows away
+/// from it. This stops a bar sitting on the boundary between two rows from
+/// flickering between them.
+fn hysteresis_height(level: f64, previous: usize, deadband: f64) -> usize {
+    if (level - previous as f64).abs() > deadband {
+        level.round() as usize
+    } else {
+        previous
+    }
+}
+
+
+/// Decide whether the display should be idle given the loudest level in a
+/// frame. It goes idle once the level drops below threshold, but only wakes
+/// up again once the level is SILENCE_HYSTERESIS_DB above it, so a level
+/// hovering around the threshold doesn't flicker between the two.
+fn silence_latch(idle: bool, level: f64, threshold: f64) -> bool {
+    // A NaN level is never loud enough to stay or wake up
+    let awake = if idle {
+        level > threshold + SILENCE_HYSTERESIS_DB
+    } else {
+        level >= threshold
+    };
+    !awake
+}
+
+
+/// Get the column of a half width spectrum to draw at column x of a window
+/// that is width columns wide, when the spectrum is mirrored out from the
+/// middle. Column 0 of the spectrum is in the middle: the middle column of an
+/// odd width, or both middle columns of an even one.
+fn fold_column(x: usize, width: usize) -> usize {
+    let right = width / 2;
+    if x >= right {
+        x - right
+    } else {
+        (width - 1) / 2 - x
+    }
+}
+
+
+/// Get the range of rows a bar covers when it is mirrored around the middle
+/// of a window that is height rows tall. Rows are counted from the bottom of
+/// the window and the range is [start, end). On an odd height the bar is
+/// centered on the middle row. On an even height the middle falls between two
+/// rows, so the bar always covers an even number of rows.
+fn mirror_rows(rows: usize, height: usize) -> (usize, usize) {
+    let center = height / 2;
+    let (start, end) = if height % 2 == 1 {
+        let half = rows / 2;
+        (center as isize - half as isize, center + half + 1)
+    } else {
+        let half = rows.div_ceil(2);
+        (center as isize - half as isize, center + half)
+    };
+
+    let start = if start < 0 { 0 } else { start as usize };
+    let end = if end > height { height } else { end };
+    (start, end)
+}
 
-    if temp_count >= band_size {
-        output.push(sum/temp_count as f64);
+
+/// Get the range of rows, counted from the bottom of the window, that a bar
+/// of the given height covers in a layout. The range is [start, end).
+fn bar_rows(layout: Layout, bar: usize, height: usize) -> (usize, usize) {
+    match layout {
+        Layout::Mirror => mirror_rows(bar + 1, height),
+        Layout::TopDown => (height.saturating_sub(bar + 1), height),
+        Layout::Bars | Layout::StereoSplit | Layout::MonoMirror => (0, bar + 1)
+    }
+}
+
+
+/// Get the (height, width) that can be drawn in a window with max_y rows and
+/// max_x columns. The last column is left empty: drawing the bottom right cell
+/// of an ncurses window moves the cursor past the end of the window, which
+/// makes the write fail (and would scroll a scrolling window), so every row is
+/// drawn one column short to keep all the rows the same width.
+///
+/// Terminal multiplexers sometimes briefly report a zero sized window, which
+/// gives a drawable size of zero rather than underflowing.
+fn drawable_size(max_y: c_int, max_x: c_int) -> (usize, usize) {
+    let height = if max_y > 0 { max_y as usize } else { 0 };
+    let width = if max_x > 1 { max_x as usize - 1 } else { 0 };
+    (height, width)
+}
+
+
+/// Fits the debug info string into the top-right corner of a window that is
+/// width columns wide. Returns the column to draw at and the part of the
+/// string that fits. On a window narrower than the string, the string is
+/// truncated instead of underflowing the column computation.
+fn fit_debug_info(debuginfo: &str, width: usize) -> (usize, &str) {
+    if debuginfo.len() > width {
+        (0, &debuginfo[..width])
+    } else {
+        (width - debuginfo.len(), debuginfo)
     }
+}
+
 
-    output
+/// Replaces every NaN or infinite value in a spectrum with silence, which
+/// should be the dB floor for decibel output or 0 for linear output. A single
+/// NaN would otherwise spread through the scaling and blank the whole frame.
+/// Returns the number of values replaced.
+pub fn sanitize_spectrum(spectrum: &mut [f64], silence: f64) -> usize {
+    let mut replaced: usize = 0;
+    for x in spectrum.iter_mut() {
+        if !x.is_finite() {
+            *x = silence;
+            replaced += 1;
+        }
+    }
+    replaced
 }
 
 
+/// Smooths a spectrum by replacing each value with the average of the window
+/// values centered on it. Near the edges the window shrinks so it only
+/// averages values that exist. A window of 0 or 1 leaves the spectrum as is.
+pub fn smooth_spectrum(input: &[f64], window: usize) -> Vec<f64> {
+    if window < 2 {
+        return input.to_vec();
+    }
+
+    let before = (window - 1) / 2;
+    let after = window / 2;
+    (0..input.len())
+        .map(|i| {
+            let start = i.saturating_sub(before);
+            let end = if i + after + 1 < input.len() { i + after + 1 } else { input.len() };
+            input[start..end].iter().fold(0.0, |sum, &x| sum + x) / (end - start) as f64
+        })
+        .collect()
+}
+
 
 /// Loops through an iterator of f64 and gets the min and max values.
 /// The min/max functions in the standard library don't work on floats.
@@ -74,7 +561,134 @@ fn get_min_max<'a, I: Iterator<Item=&'a f64>>(iter: &'a mut I) -> (f64, f64) {
     (min, max)
 }
 
-/// Resize the row buffer to width
+/// What to draw down one column of the screen: on_char in the rows
+/// [start, end), counted from the bottom, and off_char everywhere else. If
+/// there is a peak, its rows are given in the same way and a marker is drawn
+/// at each end of them that the bar doesn't cover. A clipped bar has the rows
+/// of its tips in clip.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct ColumnFill {
+    on_char: c_char,
+    off_char: c_char,
+    start: usize,
+    end: usize,
+    peak: Option<(usize, usize)>,
+    clip: Option<(usize, usize)>
+}
+
+
+impl ColumnFill {
+    /// A column that is the same character all the way down
+    fn solid(fill_char: c_char) -> ColumnFill {
+        ColumnFill { on_char: fill_char, off_char: fill_char, start: 0, end: 0, peak: None, clip: None }
+    }
+
+    /// Add the peak marker for a bar from the matching column of the peaks
+    fn with_peak(mut self, peak: Option<&Column>, layout: Layout, height: usize) -> ColumnFill {
+        if let (BAR_CHAR, Some(&Column::Bar(peak))) = (self.on_char, peak) {
+            self.peak = Some(bar_rows(layout, peak, height));
+        }
+        self
+    }
+
+    /// Mark the tip of a bar as clipped if clipped is set. The tip is the end
+    /// of the bar furthest from where it grows from, or both ends of a
+    /// mirrored bar.
+    fn with_clip(mut self, clipped: bool, layout: Layout) -> ColumnFill {
+        if clipped && self.on_char == BAR_CHAR && self.end > self.start {
+            let (top, bottom) = (self.end - 1, self.start);
+            self.clip = Some(match layout {
+                Layout::Bars | Layout::StereoSplit | Layout::MonoMirror => (top, top),
+                Layout::TopDown => (bottom, bottom),
+                Layout::Mirror => (bottom, top)
+            });
+        }
+        self
+    }
+
+    /// Get the character for row y of the column
+    fn cell(&self, y: usize) -> c_char {
+        match self.clip {
+            Some((first, second)) if y == first || y == second => return CLIP_CHAR,
+            _ => {}
+        }
+        if y >= self.start && y < self.end {
+            return self.on_char;
+        }
+        match self.peak {
+            Some((start, end)) if y == start || y + 1 == end => PEAK_CHAR,
+            _ => self.off_char
+        }
+    }
+}
+
+
+/// Work out what to draw down a column in a window that is height rows tall.
+/// Columns past the end of the data are scaling gaps, which are drawn with
+/// INIT_CHAR when show_gaps is set and with border_char otherwise.
+fn column_fill(column: Option<&Column>, layout: Layout, height: usize, border_char: c_char, show_gaps: bool) -> ColumnFill {
+    match column {
+        None if show_gaps => ColumnFill::solid(INIT_CHAR),
+        None | Some(&Column::Border) => ColumnFill::solid(border_char),
+        Some(&Column::Gap) => ColumnFill::solid(EMPTY_CHAR),
+        Some(&Column::Bar(bar)) => {
+            let (start, end) = bar_rows(layout, bar, height);
+            ColumnFill { on_char: BAR_CHAR, off_char: EMPTY_CHAR, start: start, end: end, peak: None, clip: None }
+        }
+    }
+}
+
+
+/// How the cells of a row are colored on the terminal
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RowColors {
+    /// Everything is drawn in the terminal's own color
+    Plain,
+    /// Each column is drawn in its color pair from color::rainbow_pair
+    Rainbow,
+    /// Each cell is drawn in the color pair from color::level_pair for the
+    /// shade it's drawn with, in the given scheme
+    Shades(ColorScheme)
+}
+
+
+/// Get the color pair to draw the character ch in at column x of a row that
+/// is width columns wide
+fn cell_pair(colors: RowColors, x: usize, width: usize, ch: c_char) -> c_short {
+    match colors {
+        RowColors::Plain => 0,
+        RowColors::Rainbow => color::rainbow_pair(x, width),
+        RowColors::Shades(scheme) => {
+            let shade = SHADE_CHARS.iter().position(|&c| c as c_char == ch).unwrap_or(0);
+            color::level_pair(scheme, shade, SHADE_CHARS.len())
+        }
+    }
+}
+
+
+/// Draw a row of characters on the screen at row y (counted from the top),
+/// coloring each cell with cell_pair, a run of cells at a time
+fn add_row<S: Screen>(win: &mut S, y: c_int, row: &[c_char], colors: RowColors) -> Result<c_int, c_int> {
+    if colors == RowColors::Plain {
+        return win.addbytes(y, 0, row);
+    }
+
+    let width = row.len();
+    let mut start: usize = 0;
+    while start < width {
+        let pair = cell_pair(colors, start, width, row[start]);
+        let mut end = start + 1;
+        while end < width && cell_pair(colors, end, width, row[end]) == pair {
+            end += 1;
+        }
+        try!(win.color_set(pair));
+        try!(win.addbytes(y, start as c_int, &row[start..end]));
+        start = end;
+    }
+    win.color_set(0)
+}
+
+
 fn resize_rowbuf(row: &mut Vec<c_char>, width: usize) {
     while row.len() < width {
         row.push(INIT_CHAR);
@@ -85,22 +699,94 @@ fn resize_rowbuf(row: &mut Vec<c_char>, width: usize) {
     row.shrink_to_fit();
 }
 
-pub struct Visualizer{
-   // The ncurses Window object
-   win: Window,
+pub struct Visualizer<S: Screen = Window> {
+   // The ncurses Window object, or anything else that can be drawn on like one
+   win: S,
    // A buffer of characters for a row on the screen (used to reduce calls to
    // the ncurses addstr function)
    rows: Vec<Vec<c_char>>,
+   // The rows as they were last drawn on the screen. Rows that haven't
+   // changed since the last frame aren't drawn again.
+   drawn_rows: Vec<Vec<c_char>>,
    // The width of the window the last time the animation was called
    width: usize,
    // The height of the window the last time the animation was called
-   height: usize
+   height: usize,
+   // Whether to draw the size/bar count info in the top-right corner
+   debug_overlay: bool,
+   // The character drawn between channels and where the data runs out
+   border_char: c_char,
+   // Whether to highlight columns the data doesn't reach with INIT_CHAR
+   show_gaps: bool,
+   // A fixed number of bands to draw, or None to draw one band per column
+   band_count: Option<usize>,
+   // The number of empty columns to leave between bars
+   bar_gap: usize,
+   // How the spectrum is laid out on the screen
+   layout: Layout,
+   // The colors to draw the bars with
+   color_scheme: ColorScheme,
+   // Whether the screen has been set up to draw in color
+   colors: bool,
+   // The width of the moving average applied across frequencies
+   spectrum_smoothing: usize,
+   // How the bins in each band are combined
+   bin_reduce: BinReduce,
+   // Whether the DC bin is drawn in the first band
+   include_dc: bool,
+   // How the bins are spread across the bands
+   band_spacing: BandSpacing,
+   // The edge frequencies of custom bands, which replace the band spacing
+   band_edges: Option<Vec<f64>>,
+   // Whether log bands too narrow to hold a bin borrow their neighbour's
+   fill_empty_bands: bool,
+   // How many rows a bar's level has to move from its drawn height before
+   // it is redrawn at a new height, or None to always round to the nearest
+   hysteresis: Option<f64>,
+   // The height of every bar in the last frame drawn
+   bar_heights: Vec<usize>,
+   // How many rows a frame the peak markers fall, or None to hide them
+   peak_decay: Option<f64>,
+   // How many rows a frame the peak markers rise, or None to jump straight
+   // to the bar
+   peak_attack: Option<f64>,
+   // The height of every bar's peak marker, trailing behind the bar
+   peaks: Vec<Envelope>,
+   // How many frames a clipped bar stays marked after it stops clipping, or
+   // None to not mark them
+   clip_hold: Option<usize>,
+   // How many frames each bar stays marked as clipped, counting the last
+   // one drawn
+   clip_counters: Vec<usize>,
+   // A fixed (floor, ceiling) range of dB levels to show, or None to scale
+   // each frame to its loudest band
+   db_range: Option<(f64, f64)>,
+   // A fixed (floor, ceiling) range of linear magnitudes to show on a log
+   // scale, or None
+   log_range: Option<(f64, f64)>,
+   // The level in dB below which the audio counts as silence, if set
+   silence_threshold: Option<f64>,
+   // The level a silent bin has in the spectra, which NaN and infinite
+   // values are replaced with
+   silence_level: f64,
+   // Whether the audio has gone silent and the bars are flat
+   idle: bool,
+   // The (low, high) frequencies in Hz to show, or None to show every bin
+   freq_range: Option<(f64, f64)>,
+   // The sample rate of the audio the spectra come from
+   sample_rate: f64,
+   // Whether the animation is frozen on the last frame
+   paused: bool,
+   // The spectra of each channel in the last frame rendered before pausing
+   last_frame: Vec<Vec<f64>>,
+   // How many NaN or infinite values have been scrubbed from the spectra
+   scrubbed: usize
 }
 
 
-impl Visualizer {
+impl Visualizer<Window> {
     /// Instantiate a new visualizer. Takes over the terminal with ncurses.
-    pub fn new() -> Visualizer {
+    pub fn new() -> Visualizer<Window> {
         let mut win = Window::new();
 
         // Disable the cursor so it's not moving all around the screen when the
@@ -110,102 +796,1979 @@ impl Visualizer {
             Ok(_) => {}
         }
 
-        Visualizer{
+        // Read keys without blocking the animation, and decode resize events
+        // into KEY_RESIZE
+        win.nodelay(true).unwrap();
+        win.keypad(true).unwrap();
+
+        // Set up a color pair for each color of the rainbow, keeping the
+        // terminal's own background where it allows it
+        let colors = win.has_colors() && win.start_color().is_ok();
+        if colors {
+            let background = if win.use_default_colors().is_ok() { COLOR_DEFAULT } else { COLOR_BLACK };
+            for (i, &color) in color::RAINBOW_COLORS.iter().enumerate() {
+                let _ = win.init_pair(i as c_short + 1, color, background);
+            }
+        }
+
+        let mut viz = Visualizer::with_screen(win);
+        viz.colors = colors;
+        viz
+    }
+}
+
+
+impl<S: Screen> Visualizer<S> {
+    /// Instantiate a new visualizer that draws on the given screen
+    pub fn with_screen(win: S) -> Visualizer<S> {
+        Visualizer {
             win: win,
             rows: Vec::new(),
+            drawn_rows: Vec::new(),
             width: 0,
-            height: 0
+            height: 0,
+            debug_overlay: false,
+            border_char: BORDER_CHAR,
+            show_gaps: false,
+            band_count: None,
+            bar_gap: 0,
+            layout: Layout::Bars,
+            color_scheme: ColorScheme::Monochrome,
+            colors: false,
+            spectrum_smoothing: 0,
+            bin_reduce: BinReduce::Average,
+            include_dc: true,
+            band_spacing: BandSpacing::Linear,
+            band_edges: None,
+            fill_empty_bands: true,
+            hysteresis: None,
+            bar_heights: Vec::new(),
+            peak_decay: None,
+            peak_attack: None,
+            peaks: Vec::new(),
+            clip_hold: None,
+            clip_counters: Vec::new(),
+            db_range: None,
+            log_range: None,
+            silence_threshold: None,
+            silence_level: DEFAULT_DB_FLOOR,
+            idle: false,
+            freq_range: None,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            paused: false,
+            last_frame: Vec::new(),
+            scrubbed: 0
         }
     }
 
-    /// Get the width of the scren in columns. Callers can use this to
-    /// determine the minimum amount of data the animation needs to fill the
-    /// screen.
-    pub fn get_width(&self) -> usize {
-        self.win.get_max_x().unwrap() as usize - 1
+    /// Set a fixed number of bands to draw regardless of the window width,
+    /// like a 10 or 16 band equalizer. Each band is drawn as a block of
+    /// columns. With None, there is one band for every column.
+    pub fn set_band_count(&mut self, band_count: Option<usize>) {
+        self.band_count = band_count;
     }
 
-    /// Adds or removes rows if the window size is changed.
-    fn update_row_count(&mut self, height: usize) {
-        while self.rows.len() < height {
-            self.rows.push(Vec::new());
-        }
-        while self.rows.len() > height {
-            self.rows.pop();
-        }
+    /// Get the fixed number of bands set with set_band_count, or None if
+    /// there is one band for every column
+    pub fn get_fixed_band_count(&self) -> Option<usize> {
+        self.band_count
     }
 
-    /// Resizes each of hte row buffers to the given width
-    fn resize_rowbufs(&mut self, width: usize) {
-        for row in self.rows.iter_mut() {
-            resize_rowbuf(row, width);
-        }
+    /// Set the number of empty columns to leave between each bar. This
+    /// reduces the number of bars that fit in the window. If the gap is too
+    /// large for more than one bar to fit, a single bar is drawn.
+    pub fn set_bar_gap(&mut self, cols: usize) {
+        self.bar_gap = cols;
     }
 
-    /// Do any necessary adjustments for a window size change. This gets
-    /// called when we fetch the max_yx
-    fn update_size(&mut self) {
-        let (max_y, max_x) = self.win.get_max_yx().unwrap();
-        let height: usize = max_y as usize;
-        let width: usize = max_x as usize - 1;
+    /// Get the number of empty columns left between each bar
+    pub fn get_bar_gap(&self) -> usize {
+        self.bar_gap
+    }
 
-        if self.width != width || self.height != height {
-            self.update_row_count(height);
-            self.resize_rowbufs(width);
-            self.width = width;
-            self.height = height;
-        }
+    /// Choose how the spectrum is laid out on the screen
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
     }
 
-    /// Render a single frame of the animation
-    pub fn render_frame(&mut self, data: &[f64]) -> Result<(), c_int> {
-        self.update_size();
+    /// Get how the spectrum is laid out on the screen
+    pub fn get_layout(&self) -> Layout {
+        self.layout
+    }
 
-        let data = scale_fft_output(data, self.width as usize);
-        let (_, max_val) = get_min_max(&mut data.iter());
-        let scaled: Vec<usize> = data.iter()
-            .map(|&x| {
-                if x < 1.0 {
-                    0
-                } else {
-                    ((x / max_val) * (self.height as f64 - 1.0)) as usize
-                }
-            })
-            .collect();
+    /// Get the colors the bars are drawn with
+    pub fn get_color_scheme(&self) -> ColorScheme {
+        self.color_scheme
+    }
 
-        for (y, row) in self.rows.iter_mut().enumerate().rev() {
-            for (x, val) in row.iter_mut().enumerate() {
-                *val = if x >= scaled.len() {
-                    BORDER_CHAR
-                } else {
-                    let val = scaled[x];
-                    if val >= y {
-                        BAR_CHAR
-                    } else {
-                        EMPTY_CHAR
-                    }
-                };
-            }
+    /// Smooth each frame across frequencies with a centered moving average of
+    /// window bins before it is scaled to the screen. A window of 0 or 1
+    /// disables the smoothing.
+    pub fn set_spectrum_smoothing(&mut self, window: usize) {
+        self.spectrum_smoothing = window;
+    }
 
-            match self.win.addbytes((self.height - y -1) as c_int, 0, row) {
-                Err(_) => {
-                    // Happens when window is resized. Skip the frame.
-                    return Ok(());
-                },
-                Ok(_) => { }
-            }
-        }
+    /// Get the width of the moving average applied across frequencies
+    pub fn get_spectrum_smoothing(&self) -> usize {
+        self.spectrum_smoothing
+    }
 
-        // Add some info so you can see the decisions it's making
-        let debuginfo = format!(" width: {}, height: {}, bars: {} ", self.width, self.height, scaled.len());
-        let _ = self.win.addstr(0, (self.width - debuginfo.len()) as c_int, &debuginfo[..]);
+    /// Freeze the animation on the last frame. While paused, render_frame
+    /// keeps drawing the frame from before pausing, so it survives a resize.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Check whether the animation is frozen on the last frame
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Choose how the FFT bins in each band are combined. The default is to
+    /// average them.
+    pub fn set_bin_reduce(&mut self, bin_reduce: BinReduce) {
+        self.bin_reduce = bin_reduce;
+    }
+
+    /// Get how the FFT bins in each band are combined
+    pub fn get_bin_reduce(&self) -> BinReduce {
+        self.bin_reduce
+    }
+
+    /// Choose whether the DC bin, the first one, is drawn as part of the
+    /// first band. It usually only shows the audio's constant offset, so
+    /// leaving it out stops it from swamping the bass. It is included by
+    /// default. Logarithmic bands never include it.
+    pub fn set_include_dc(&mut self, enabled: bool) {
+        self.include_dc = enabled;
+    }
+
+    /// Check whether the DC bin is drawn
+    pub fn get_include_dc(&self) -> bool {
+        self.include_dc
+    }
+
+    /// Set how the FFT bins are spread across the bands
+    pub fn set_band_spacing(&mut self, band_spacing: BandSpacing) {
+        self.band_spacing = band_spacing;
+    }
 
-        // Calling refresh makes it actually take effect
-        try!(self.win.refresh());
+    /// Get how the FFT bins are spread across the bands
+    pub fn get_band_spacing(&self) -> BandSpacing {
+        self.band_spacing
+    }
 
+    /// Draw custom bands with the given edge frequencies in Hz instead of
+    /// spacing them out, so n edges give n - 1 bands (see
+    /// scale_fft_output_custom). The edges also replace the band count,
+    /// frequency range and DC settings. Bands that don't fit in the window
+    /// are cut off the top. Fails if the edges don't suit the sample rate.
+    pub fn set_band_edges(&mut self, edges: &[f64]) -> Result<(), BandEdgeError> {
+        try!(check_band_edges(edges, self.sample_rate));
+        self.band_edges = Some(edges.to_vec());
         Ok(())
     }
-}
 
+    /// Go back to spacing the bands out with the band spacing
+    pub fn clear_band_edges(&mut self) {
+        self.band_edges = None;
+    }
+
+    /// Get the edge frequencies of the custom bands, or None if the bands are
+    /// spaced out with the band spacing
+    pub fn get_band_edges(&self) -> Option<&[f64]> {
+        self.band_edges.as_ref().map(|edges| &edges[..])
+    }
+
+    /// Choose whether logarithmic bands too narrow to hold any bins show the
+    /// bin they fall in, which is the default, or stay empty. Leaving them
+    /// empty shows a comb of gaps in the bass on a small FFT.
+    pub fn set_fill_empty_bands(&mut self, enabled: bool) {
+        self.fill_empty_bands = enabled;
+    }
+
+    /// Check whether empty logarithmic bands borrow a bin
+    pub fn get_fill_empty_bands(&self) -> bool {
+        self.fill_empty_bands
+    }
+
+    /// Only move a bar to a new row once its level is more than deadband rows
+    /// away from the row it's drawn at, which stops bars near the boundary
+    /// between two rows from flickering. A deadband around 0.4 works well.
+    /// None always rounds to the nearest row.
+    pub fn set_hysteresis(&mut self, deadband: Option<f64>) {
+        self.hysteresis = deadband;
+    }
+
+    /// Get the deadband in rows a bar's level has to move past before it is
+    /// drawn at a new row, or None if it always rounds to the nearest row
+    pub fn get_hysteresis(&self) -> Option<f64> {
+        self.hysteresis
+    }
+
+    /// Mark the recent peak of each bar, letting the marker fall decay rows
+    /// each frame once the bar drops below it. This is independent of how the
+    /// bars themselves move. None hides the markers, which is the default.
+    pub fn set_peak_hold(&mut self, decay: Option<f64>) {
+        self.peak_decay = decay;
+        for peak in self.peaks.iter_mut() {
+            peak.release = decay;
+        }
+    }
+
+    /// Get how many rows each frame the peak markers fall, or None if they
+    /// are hidden
+    pub fn get_peak_hold(&self) -> Option<f64> {
+        self.peak_decay
+    }
+
+    /// Set how many rows each frame a peak marker can rise to catch up with a
+    /// taller bar. None, the default, moves it straight to the top of the bar.
+    pub fn set_peak_attack(&mut self, attack: Option<f64>) {
+        self.peak_attack = attack;
+        for peak in self.peaks.iter_mut() {
+            peak.attack = attack;
+        }
+    }
 
-unsafe impl Send for Visualizer {}
+    /// Get how many rows each frame a peak marker can rise, or None if it
+    /// jumps straight to the top of the bar
+    pub fn get_peak_attack(&self) -> Option<f64> {
+        self.peak_attack
+    }
+
+    /// Show a fixed range of levels, from floor dB at the bottom of the window
+    /// to ceiling dB at the top, so the bars show absolute levels that can be
+    /// compared between frames. Expects the FFT output in decibels.
+    pub fn set_db_range(&mut self, floor: f64, ceiling: f64) {
+        self.db_range = Some((floor, ceiling));
+        self.log_range = None;
+    }
+
+    /// Go back to scaling each frame so its loudest band reaches the top
+    pub fn clear_db_range(&mut self) {
+        self.db_range = None;
+    }
+
+    /// Get the fixed (floor, ceiling) range of dB levels shown, or None if
+    /// each frame is scaled to its loudest band
+    pub fn get_db_range(&self) -> Option<(f64, f64)> {
+        self.db_range
+    }
+
+    /// Show a fixed range of linear magnitudes on a logarithmic scale, from
+    /// floor at the bottom of the window to ceiling at the top. This gives the
+    /// even spread of a dB range while keeping the FFT output as magnitudes.
+    /// The floor has to be above zero. Replaces any dB range.
+    pub fn set_log_range(&mut self, floor: f64, ceiling: f64) {
+        self.log_range = Some((floor, ceiling));
+        self.db_range = None;
+    }
+
+    /// Stop showing magnitudes on a logarithmic scale
+    pub fn clear_log_range(&mut self) {
+        self.log_range = None;
+    }
+
+    /// Get the (floor, ceiling) range of magnitudes shown on a logarithmic
+    /// scale, or None if it isn't used
+    pub fn get_log_range(&self) -> Option<(f64, f64)> {
+        self.log_range
+    }
+
+    /// Mark the tip of any bar that reaches the ceiling of a fixed dB or log
+    /// range, and keep it marked for frames frames after it drops back. Bars
+    /// scaled to the loudest band always reach the top, so they are never
+    /// marked. None turns the marks off, which is the default.
+    pub fn set_clip_hold(&mut self, frames: Option<usize>) {
+        self.clip_hold = frames;
+    }
+
+    /// Get how many frames a clip mark stays after the bar drops back, or
+    /// None if bars aren't marked
+    pub fn get_clip_hold(&self) -> Option<usize> {
+        self.clip_hold
+    }
+
+    /// Flatten the bars while the loudest level in a frame is below db, so
+    /// silence isn't scaled up into a screen of noise. Expects the FFT output
+    /// in decibels. The display stays idle until the level rises a few dB
+    /// above the threshold.
+    pub fn set_silence_threshold(&mut self, db: f64) {
+        self.silence_threshold = Some(db);
+    }
+
+    /// Stop detecting silence
+    pub fn clear_silence_threshold(&mut self) {
+        self.silence_threshold = None;
+        self.idle = false;
+    }
+
+    /// Get the level in dB below which the audio counts as silence
+    pub fn get_silence_threshold(&self) -> Option<f64> {
+        self.silence_threshold
+    }
+
+    /// Set the level a silent bin has in the spectra, which NaN and infinite
+    /// values are replaced with. It should match the FFT's output (see
+    /// AudioFft::get_silence_level). The default is DEFAULT_DB_FLOOR, for
+    /// decibels.
+    pub fn set_silence_level(&mut self, level: f64) {
+        self.silence_level = level;
+    }
+
+    /// Get the level a silent bin has in the spectra
+    pub fn get_silence_level(&self) -> f64 {
+        self.silence_level
+    }
+
+    /// Check whether the last frame was silent, leaving the bars flat
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Only show the bins from low Hz to high Hz, spreading them across the
+    /// whole window. The bins are taken to run from 0 Hz to the Nyquist
+    /// frequency of the sample rate.
+    pub fn set_freq_range(&mut self, low: f64, high: f64) {
+        self.freq_range = Some((low, high));
+    }
+
+    /// Go back to showing every bin
+    pub fn clear_freq_range(&mut self) {
+        self.freq_range = None;
+    }
+
+    /// Get the (low, high) frequencies shown, or None if every bin is shown
+    pub fn get_freq_range(&self) -> Option<(f64, f64)> {
+        self.freq_range
+    }
+
+    /// Set the sample rate of the audio, which the frequency range needs to
+    /// find its bins. The default is 44100 Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Choose the colors to draw the bars with. The terminal can only show
+    /// FrequencyRainbow, and only if it supports color; everything else is
+    /// drawn in the terminal's own color. Images show every scheme.
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.color_scheme = color_scheme;
+        // The characters may not change, but their colors might
+        self.invalidate_drawn_rows();
+    }
+
+    /// Enable or disable the debug info in the top-right corner of the screen.
+    /// It is off by default.
+    pub fn set_debug_overlay(&mut self, enabled: bool) {
+        self.debug_overlay = enabled;
+        // The overlay was drawn over the top row, so it needs to be redrawn
+        self.invalidate_drawn_rows();
+    }
+
+    /// Get whether the debug info is shown
+    pub fn get_debug_overlay(&self) -> bool {
+        self.debug_overlay
+    }
+
+    /// Set the character drawn between channels and in any columns the data
+    /// doesn't reach. It must be ASCII. The default is a space.
+    pub fn set_border_char(&mut self, border_char: char) {
+        self.border_char = border_char as c_char;
+    }
+
+    /// Get the character drawn between channels
+    pub fn get_border_char(&self) -> char {
+        self.border_char as u8 as char
+    }
+
+    /// Draw the columns the data doesn't reach with INIT_CHAR instead of the
+    /// border character, which makes a mismatch between the bands and the
+    /// window width easy to spot. It is off by default.
+    pub fn set_show_gaps(&mut self, enabled: bool) {
+        self.show_gaps = enabled;
+    }
+
+    /// Check whether the columns the data doesn't reach are drawn with
+    /// INIT_CHAR
+    pub fn get_show_gaps(&self) -> bool {
+        self.show_gaps
+    }
+
+    /// Get the width of the scren in columns. Callers can use this to
+    /// determine the minimum amount of data the animation needs to fill the
+    /// screen. This is one less than the terminal width because the last
+    /// column is never drawn in (see drawable_size).
+    pub fn get_width(&self) -> usize {
+        self.get_size().1
+    }
+
+    /// Get the height of the screen in rows
+    pub fn get_height(&self) -> usize {
+        self.get_size().0
+    }
+
+    /// Get the drawable (height, width) of the screen as it is right now
+    fn get_size(&self) -> (usize, usize) {
+        match self.win.get_max_yx() {
+            Ok((max_y, max_x)) => drawable_size(max_y, max_x),
+            Err(_) => (0, 0)
+        }
+    }
+
+    /// Get how many NaN or infinite values have been replaced in the spectra
+    /// given to the visualizer. Anything but 0 points to a bug upstream.
+    pub fn get_scrubbed_count(&self) -> usize {
+        self.scrubbed
+    }
+
+    /// Get the number of bands that will be drawn at the current window size.
+    /// This is updated as soon as a resize is read by read_key.
+    pub fn get_band_count(&self) -> usize {
+        self.channel_widths(self.width).iter().map(|&width| self.band_layout(width).0).sum()
+    }
+
+    /// Read the next key the user pressed without blocking. Returns None if
+    /// no key was pressed. Resize events are handled here by adjusting to the
+    /// new size straight away, so the next frame is never drawn with stale
+    /// dimensions, and are not returned.
+    pub fn read_key(&mut self) -> Option<c_int> {
+        loop {
+            match self.win.getch() {
+                Some(KEY_RESIZE) => self.update_size(),
+                key => return key
+            }
+        }
+    }
+
+    /// Adds or removes rows if the window size is changed.
+    fn update_row_count(&mut self, height: usize) {
+        while self.rows.len() < height {
+            self.rows.push(Vec::new());
+        }
+        while self.rows.len() > height {
+            self.rows.pop();
+        }
+    }
+
+    /// Resizes each of hte row buffers to the given width
+    fn resize_rowbufs(&mut self, width: usize) {
+        for row in self.rows.iter_mut() {
+            resize_rowbuf(row, width);
+        }
+    }
+
+    /// Forget what was drawn on the screen so every row is drawn on the next
+    /// frame
+    fn invalidate_drawn_rows(&mut self) {
+        self.drawn_rows = vec![Vec::new(); self.rows.len()];
+    }
+
+    /// Do any necessary adjustments for a window size change. This gets
+    /// called when we fetch the max_yx
+    fn update_size(&mut self) {
+        let (height, width) = self.get_size();
+
+        if self.width != width || self.height != height {
+            self.update_row_count(height);
+            self.resize_rowbufs(width);
+            self.invalidate_drawn_rows();
+            self.width = width;
+            self.height = height;
+        }
+    }
+
+    /// Get the number of bands that fit in width columns, along with the
+    /// number of columns to leave between them
+    fn band_layout(&self, width: usize) -> (usize, usize) {
+        // Fixed bands always have at least one column between them
+        let gap = match self.fixed_bands() {
+            Some(_) if self.bar_gap == 0 => 1,
+            _ => self.bar_gap
+        };
+        let max_bars = bar_count(width, gap);
+        let band_count = match self.fixed_bands() {
+            Some(band_count) if band_count < max_bars => band_count,
+            _ => max_bars
+        };
+        (band_count, gap)
+    }
+
+    /// Get the number of bands drawn whatever the window width, from the
+    /// custom band edges or the fixed band count
+    fn fixed_bands(&self) -> Option<usize> {
+        match self.band_edges {
+            Some(ref edges) => Some(edges.len() - 1),
+            None => self.band_count
+        }
+    }
+
+    /// Lay out a spectrum's bar heights across width columns
+    fn spectrum_columns(&self, heights: &[usize], width: usize) -> Vec<Column> {
+        let (_, gap) = self.band_layout(width);
+        let spread = self.fixed_bands().is_some() || gap > 0;
+        layout_columns(heights, width, spread, gap)
+    }
+
+    /// Turns the spectra of each channel into the columns to draw in a window
+    /// that is width columns wide and height rows tall. The StereoSplit layout
+    /// needs exactly two channels and every other layout needs exactly one.
+    /// previous holds the height of each bar in the last frame, for hysteresis.
+    /// Returns the columns and the height of each channel's bars.
+    fn compute_bands(&self, channels: &[&[f64]], width: usize, height: usize, previous: &[usize]) -> Result<(Vec<Column>, Vec<Vec<usize>>), RenderError> {
+        let required = match self.layout {
+            Layout::Bars | Layout::Mirror | Layout::TopDown | Layout::MonoMirror => 1,
+            Layout::StereoSplit => 2
+        };
+        if channels.len() != required {
+            return Err(RenderError::ChannelCount(channels.len()));
+        }
+
+        let widths = self.channel_widths(width);
+        let spectra: Vec<Vec<f64>> = try!(channels.iter().zip(widths.iter())
+            .map(|(&data, &width)| -> Result<Vec<f64>, RenderError> {
+                let smoothed = smooth_spectrum(data, self.spectrum_smoothing);
+                let (start, end) = self.bin_range(smoothed.len(), self.sample_rate);
+                let band_count = self.band_layout(width).0;
+                if let Some(ref edges) = self.band_edges {
+                    let mut bands = try!(scale_fft_output_custom(&smoothed[..], edges, self.sample_rate, self.bin_reduce)
+                        .map_err(RenderError::BandEdges));
+                    bands.truncate(band_count);
+                    return Ok(bands);
+                }
+                Ok(match self.band_spacing {
+                    BandSpacing::Linear => scale_fft_output(&smoothed[start..end], band_count, self.bin_reduce),
+                    BandSpacing::Logarithmic => scale_fft_output_log(
+                        &smoothed[..end], start, band_count, self.bin_reduce, self.fill_empty_bands)
+                })
+            })
+            .collect());
+
+        // Every channel is normalized against the same max so they're
+        // comparable
+        let (_, max_val) = get_min_max(&mut spectra.iter().flat_map(|spectrum| spectrum.iter()));
+
+        // The bars only line up with the last frame's if there are as many
+        let bar_count: usize = spectra.iter().map(|spectrum| spectrum.len()).sum();
+        let previous = if previous.len() == bar_count { previous } else { &[] };
+
+        let mut bar_heights: Vec<Vec<usize>> = Vec::with_capacity(spectra.len());
+        let mut first_bar: usize = 0;
+        for spectrum in spectra.iter() {
+            let heights: Vec<usize> = spectrum.iter().enumerate()
+                .map(|(i, &x)| {
+                    if self.idle {
+                        return 0;
+                    }
+                    let level = match (self.db_range, self.log_range) {
+                        (Some((floor, ceiling)), _) => db_bar_level(x, floor, ceiling, height),
+                        (None, Some((floor, ceiling))) => log_bar_level(x, floor, ceiling, height),
+                        (None, None) => bar_level(x, max_val, height)
+                    };
+                    match (self.hysteresis, previous.get(first_bar + i)) {
+                        (Some(deadband), Some(&drawn)) => hysteresis_height(level, drawn, deadband),
+                        _ => level.round() as usize
+                    }
+                })
+                .collect();
+            first_bar += heights.len();
+            bar_heights.push(heights);
+        }
+
+        Ok((self.arrange_columns(&bar_heights[..], width), bar_heights))
+    }
+
+    /// Get the [start, end) range of bins drawn from a spectrum of len bins
+    /// at sample_rate, after the frequency range and DC settings
+    fn bin_range(&self, len: usize, sample_rate: f64) -> (usize, usize) {
+        let (start, end) = match self.freq_range {
+            Some((low, high)) => freq_range_bins(len, low, high, sample_rate),
+            None => (0, len)
+        };
+        // The Nyquist bin at the end is always kept
+        let start = if self.include_dc || start > 0 || end < 2 { start } else { 1 };
+        (start, end)
+    }
+
+    /// Get the (low, high) frequencies in Hz covered by band band_index when
+    /// a spectrum of bin_count bins at sample_rate is split into band_count
+    /// bands with the current settings. Each bin is taken to reach halfway to
+    /// its neighbours, so neighbouring bands share an edge. A logarithmic band
+    /// too narrow to hold any bins, or a band past the last one, has no
+    /// width. Custom bands are given by their edges, whatever the band count.
+    pub fn band_to_frequency(&self, band_index: usize, band_count: usize, bin_count: usize, sample_rate: f64) -> (f64, f64) {
+        if let Some(ref edges) = self.band_edges {
+            let last = edges.len() - 1;
+            let edge = |i: usize| edges[::std::cmp::min(i, last)];
+            return (edge(band_index), edge(band_index + 1));
+        }
+
+        let nyquist = sample_rate / 2.0;
+        if bin_count < 2 {
+            return (0.0, nyquist);
+        }
+        let (start, end) = self.bin_range(bin_count, sample_rate);
+
+        // The [first, last) bins in the band, the same as the binning uses
+        let (first, last) = match self.band_spacing {
+            BandSpacing::Linear => {
+                let bins = end - start;
+                if band_count >= bins {
+                    // Every bin is its own band
+                    (start + band_index, start + band_index + 1)
+                } else {
+                    (start + band_index * bins / band_count, start + (band_index + 1) * bins / band_count)
+                }
+            },
+            BandSpacing::Logarithmic => {
+                let low = ::std::cmp::max(start, 1) as f64;
+                let edge = |i: usize| (low * (end as f64 / low).powf(i as f64 / band_count as f64)).floor() as usize;
+                (edge(band_index), edge(band_index + 1))
+            }
+        };
+
+        let bin_width = nyquist / (bin_count - 1) as f64;
+        let to_frequency = |bin: usize| ((::std::cmp::min(bin, end) as f64 - 0.5) * bin_width).max(0.0).min(nyquist);
+        (to_frequency(first), to_frequency(last))
+    }
+
+    /// Get the width each channel gets in a window that is width columns wide
+    fn channel_widths(&self, width: usize) -> Vec<usize> {
+        match self.layout {
+            Layout::Bars | Layout::Mirror | Layout::TopDown => vec![width],
+            Layout::StereoSplit => {
+                let (left, right) = stereo_widths(width);
+                vec![left, right]
+            },
+            // The spectrum is laid out across half the window, including the
+            // middle column of an odd width, and then folded out
+            Layout::MonoMirror => vec![width - width / 2]
+        }
+    }
+
+    /// Lay out the bars of each channel across a window that is width
+    /// columns wide, with a border between the channels
+    fn arrange_columns(&self, heights: &[Vec<usize>], width: usize) -> Vec<Column> {
+        let mut columns: Vec<Column> = Vec::with_capacity(width);
+        for (heights, width) in heights.iter().zip(self.channel_widths(width)) {
+            if !columns.is_empty() {
+                columns.push(Column::Border);
+            }
+            columns.extend(self.spectrum_columns(&heights[..], width));
+        }
+
+        if self.layout == Layout::MonoMirror {
+            return (0..width)
+                .map(|x| columns.get(fold_column(x, width)).cloned().unwrap_or(Column::Border))
+                .collect();
+        }
+        columns
+    }
+
+    /// Render a single frame of the animation
+    pub fn render_frame(&mut self, data: &[f64]) -> Result<(), RenderError> {
+        self.render_channels(&[data])
+    }
+
+    /// Render a single frame of the animation from the spectra of each audio
+    /// channel. The StereoSplit layout needs exactly two channels and every
+    /// other layout needs exactly one. While paused, the new spectra are
+    /// ignored and the last frame from before pausing is drawn again.
+    pub fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        if !self.paused {
+            self.store_frame(channels);
+        }
+        self.redraw()
+    }
+
+    /// Copy the spectra of each channel into last_frame, scrubbing any NaN
+    /// or infinite values to silence on the way
+    fn store_frame(&mut self, channels: &[&[f64]]) {
+        self.last_frame.truncate(channels.len());
+        while self.last_frame.len() < channels.len() {
+            self.last_frame.push(Vec::new());
+        }
+        let silence = self.silence_level;
+        for (stored, &channel) in self.last_frame.iter_mut().zip(channels.iter()) {
+            stored.clear();
+            stored.extend_from_slice(channel);
+            self.scrubbed += sanitize_spectrum(&mut stored[..], silence);
+        }
+    }
+
+    /// Draw the last frame again, for instance after a resize while paused.
+    /// Does nothing if no frame has been rendered yet.
+    pub fn redraw(&mut self) -> Result<(), RenderError> {
+        if self.last_frame.is_empty() {
+            return Ok(());
+        }
+
+        // Move the frame out while it's drawn so it can be borrowed alongside
+        // the rest of the visualizer
+        let frame = mem::take(&mut self.last_frame);
+        let result = {
+            let channels: Vec<&[f64]> = frame.iter().map(|channel| &channel[..]).collect();
+            self.draw(&channels[..])
+        };
+        self.last_frame = frame;
+        result
+    }
+
+    /// Draw the spectra of each channel on the screen
+    fn draw(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        self.update_size();
+
+        // Nothing can be drawn until the window has some area again
+        if self.width == 0 || self.height == 0 {
+            return Ok(());
+        }
+
+        let height = self.height;
+        self.update_idle(channels);
+        let (columns, channel_heights) = try!(self.compute_bands(channels, self.width, height, &self.bar_heights[..]));
+        self.bar_heights = channel_heights.iter().flat_map(|heights| heights.iter().cloned()).collect();
+        let bars = self.bar_heights.len();
+        let peak_columns = self.update_peaks(&channel_heights[..]);
+        let clipped_columns = self.update_clips(&channel_heights[..], height);
+
+        // Decide what each column looks like once, rather than for every cell
+        let fills: Vec<ColumnFill> = (0..self.width)
+            .map(|x| {
+                column_fill(columns.get(x), self.layout, height, self.border_char, self.show_gaps)
+                    .with_peak(peak_columns.get(x), self.layout, height)
+                    .with_clip(clipped_columns.get(x) == Some(&true), self.layout)
+            })
+            .collect();
+        for (y, row) in self.rows.iter_mut().enumerate() {
+            for (val, fill) in row.iter_mut().zip(fills.iter()) {
+                *val = fill.cell(y);
+            }
+        }
+
+        self.put_rows(bars)
+    }
+
+    /// Check whether the frame is silent and latch the idle state
+    fn update_idle(&mut self, channels: &[&[f64]]) {
+        if let Some(threshold) = self.silence_threshold {
+            let loudest = channels.iter()
+                .flat_map(|channel| channel.iter())
+                .fold(f64::NEG_INFINITY, |max, &x| if x > max { x } else { max });
+            self.idle = silence_latch(self.idle, loudest, threshold);
+        }
+    }
+
+    /// Move the peak markers towards the new bar heights and lay them out in
+    /// columns like the bars. While paused the markers stay where they are
+    /// and are only laid out again. The columns are empty if peaks aren't
+    /// shown.
+    fn update_peaks(&mut self, channel_heights: &[Vec<usize>]) -> Vec<Column> {
+        if self.peak_decay.is_none() {
+            self.peaks.clear();
+            return Vec::new();
+        }
+
+        if self.peaks.len() != self.bar_heights.len() {
+            // The bars have changed after a resize, so start the peaks again
+            // from the bars
+            let (attack, release) = (self.peak_attack, self.peak_decay);
+            self.peaks = self.bar_heights.iter()
+                .map(|&bar| Envelope::new(attack, release, bar as f64))
+                .collect();
+        } else if !self.paused {
+            for (peak, &bar) in self.peaks.iter_mut().zip(self.bar_heights.iter()) {
+                peak.process(bar as f64);
+            }
+        }
+
+        let mut peak_heights: Vec<Vec<usize>> = Vec::with_capacity(channel_heights.len());
+        let mut first_bar: usize = 0;
+        for heights in channel_heights.iter() {
+            let peaks = &self.peaks[first_bar..first_bar + heights.len()];
+            peak_heights.push(peaks.iter().map(|peak| peak.value.round() as usize).collect());
+            first_bar += heights.len();
+        }
+        self.arrange_columns(&peak_heights[..], self.width)
+    }
+
+    /// Count down how long each bar stays marked as clipped, restarting the
+    /// count for bars at the top of the window. The count doesn't move while
+    /// paused, so the marks stay as they were. Returns whether each column
+    /// is part of a marked bar, which is empty if clips aren't marked.
+    fn update_clips(&mut self, channel_heights: &[Vec<usize>], height: usize) -> Vec<bool> {
+        let hold = match (self.clip_hold, self.db_range.or(self.log_range)) {
+            (Some(hold), Some(_)) => hold,
+            _ => {
+                self.clip_counters.clear();
+                return Vec::new();
+            }
+        };
+        if self.clip_counters.len() != self.bar_heights.len() {
+            self.clip_counters = vec![0; self.bar_heights.len()];
+        }
+
+        let mut marks: Vec<usize> = Vec::with_capacity(self.bar_heights.len());
+        for (counter, &bar) in self.clip_counters.iter_mut().zip(self.bar_heights.iter()) {
+            // The count includes the current frame, so a bar is marked for as
+            // long as it's above 0
+            if bar + 1 >= height {
+                *counter = hold + 1;
+            } else if !self.paused {
+                *counter = counter.saturating_sub(1);
+            }
+            marks.push(if *counter > 0 { 1 } else { 0 });
+        }
+
+        // The marks are laid out like bars one row tall, so they land in the
+        // same columns as the bars they belong to
+        let mut clip_heights: Vec<Vec<usize>> = Vec::with_capacity(channel_heights.len());
+        let mut first_bar: usize = 0;
+        for heights in channel_heights.iter() {
+            clip_heights.push(marks[first_bar..first_bar + heights.len()].to_vec());
+            first_bar += heights.len();
+        }
+        self.arrange_columns(&clip_heights[..], self.width)
+            .into_iter()
+            .map(|column| column == Column::Bar(1))
+            .collect()
+    }
+
+    /// Put the row buffers on the screen, skipping the rows that haven't
+    /// changed since they were last drawn, then the debug overlay
+    fn put_rows(&mut self, bars: usize) -> Result<(), RenderError> {
+        let colors = if self.colors && self.color_scheme == ColorScheme::FrequencyRainbow {
+            RowColors::Rainbow
+        } else {
+            RowColors::Plain
+        };
+        self.put_colored_rows(bars, colors)
+    }
+
+    /// Put the row buffers on the screen like put_rows, coloring them with
+    /// colors. The colors of a row can only depend on its characters, or the
+    /// rows that haven't changed would be left in the wrong colors.
+    fn put_colored_rows(&mut self, bars: usize, colors: RowColors) -> Result<(), RenderError> {
+        for (y, row) in self.rows.iter().enumerate().rev() {
+            if self.drawn_rows.get(y) == Some(row) {
+                continue;
+            }
+
+            match add_row(&mut self.win, (self.height - y -1) as c_int, row, colors) {
+                Err(_) => {
+                    // Happens when window is resized. Skip the frame and
+                    // draw everything on the next one.
+                    self.invalidate_drawn_rows();
+                    return Ok(());
+                },
+                Ok(_) => { }
+            }
+            if let Some(drawn) = self.drawn_rows.get_mut(y) {
+                drawn.clone_from(row);
+            }
+        }
+
+        // Add some info so you can see the decisions it's making
+        if self.debug_overlay {
+            let mut debuginfo = format!(" width: {}, height: {}, bars: {} ", self.width, self.height, bars);
+            if self.scrubbed > 0 {
+                debuginfo.push_str(&format!("scrubbed: {} ", self.scrubbed));
+            }
+            let (col, text) = fit_debug_info(&debuginfo[..], self.width);
+            let _ = self.win.addstr(0, col as c_int, text);
+        }
+
+        // Put the whole frame on the display at once so it never tears
+        try!(self.win.noutrefresh().map_err(RenderError::Curses));
+        try!(self.win.doupdate().map_err(RenderError::Curses));
+
+        Ok(())
+    }
+
+    /// Work out the height of each channel's bars in a window of the given
+    /// size without drawing anything. This is the band transform that
+    /// render_channels draws from, without the hysteresis against the last
+    /// frame.
+    pub fn compute_bar_heights(&self, channels: &[&[f64]], width: usize, height: usize) -> Result<Vec<Vec<usize>>, RenderError> {
+        self.compute_bands(channels, width, height, &[]).map(|(_, heights)| heights)
+    }
+
+    /// Draw the same bars that render_channels would draw on a screen of the
+    /// given size into an image instead, with one pixel for every character.
+    /// The bars are colored with the visualizer's color scheme on a black
+    /// background.
+    pub fn render_to_image(&self, channels: &[&[f64]], width: usize, height: usize) -> Result<Image, RenderError> {
+        let (columns, _) = try!(self.compute_bands(channels, width, height, &[]));
+        let mut image = Image::new(width, height);
+
+        for (x, column) in columns.iter().enumerate() {
+            if let Column::Bar(bar) = *column {
+                let (start, end) = bar_rows(self.layout, bar, height);
+                for y in start..end {
+                    // Colors follow the bar away from the edge it grows from
+                    let level_row = match self.layout {
+                        Layout::TopDown => height - y - 1,
+                        _ => y
+                    };
+                    let color = self.color_scheme.get_color(level_row, height, x, width);
+                    image.set_pixel(x, height - y - 1, color);
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+
+unsafe impl<S: Screen> Send for Visualizer<S> {}
+
+
+/// Something that draws frames of audio spectra
+pub trait Renderer {
+    /// Render a frame from the spectra of each audio channel
+    fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError>;
+
+    /// Render a frame from a single spectrum
+    fn render_frame(&mut self, data: &[f64]) -> Result<(), RenderError> {
+        self.render_channels(&[data])
+    }
+}
+
+
+impl<S: Screen> Renderer for Visualizer<S> {
+    fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        Visualizer::render_channels(self, channels)
+    }
+}
+
+
+/// Draws a waterfall of the last few spectra: frequency runs across the
+/// screen and time runs up it, with the newest spectrum on the bottom row.
+/// Each cell is shaded by how loud its band was, and on a color terminal it
+/// is also colored by its level in the visualizer's color scheme (see
+/// color::level_pair). The bands are worked out by a Visualizer, so all of
+/// its band settings apply.
+pub struct SpectrogramRenderer<S: Screen = Window> {
+    viz: Visualizer<S>,
+    // The shade of every column in each of the frames on screen, newest
+    // first. None is a column without a bar, like a gap.
+    history: VecDeque<Vec<Option<usize>>>
+}
+
+
+impl SpectrogramRenderer<Window> {
+    /// Instantiate a new spectrogram. Takes over the terminal with ncurses.
+    pub fn new() -> SpectrogramRenderer<Window> {
+        SpectrogramRenderer::with_visualizer(Visualizer::new())
+    }
+}
+
+
+impl Default for SpectrogramRenderer<Window> {
+    fn default() -> SpectrogramRenderer<Window> {
+        SpectrogramRenderer::new()
+    }
+}
+
+
+impl<S: Screen> SpectrogramRenderer<S> {
+    /// Instantiate a new spectrogram that works out its bands with viz and
+    /// draws on its screen
+    pub fn with_visualizer(viz: Visualizer<S>) -> SpectrogramRenderer<S> {
+        SpectrogramRenderer {
+            viz: viz,
+            history: VecDeque::new()
+        }
+    }
+
+    /// Borrow the visualizer to change its settings
+    pub fn get_visualizer_mut(&mut self) -> &mut Visualizer<S> {
+        &mut self.viz
+    }
+
+    /// Add the shades of a new frame to the bottom of the history, scrolling
+    /// the oldest off the top once there are height of them
+    fn push_frame(&mut self, shades: Vec<Option<usize>>, height: usize) {
+        // A resize changes what every column means
+        if self.history.front().is_some_and(|newest| newest.len() != shades.len()) {
+            self.history.clear();
+        }
+        self.history.push_front(shades);
+        self.history.truncate(height);
+    }
+
+    /// Add a frame and draw the spectrogram. Bad values in the spectra are
+    /// scrubbed the same way the visualizer scrubs them.
+    pub fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        self.viz.store_frame(channels);
+
+        // Move the frame out while it's drawn so it can be borrowed alongside
+        // the visualizer
+        let frame = mem::take(&mut self.viz.last_frame);
+        let result = {
+            let channels: Vec<&[f64]> = frame.iter().map(|channel| &channel[..]).collect();
+            self.draw(&channels[..])
+        };
+        self.viz.last_frame = frame;
+        result
+    }
+
+    /// Add the bands of a frame to the history and draw the spectrogram
+    fn draw(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        self.viz.update_size();
+        let (width, height) = (self.viz.width, self.viz.height);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        self.viz.update_idle(channels);
+        let (columns, channel_heights) = try!(self.viz.compute_bands(channels, width, SHADE_CHARS.len(), &[]));
+        let bars = channel_heights.iter().map(|heights| heights.len()).sum();
+        let shades: Vec<Option<usize>> = (0..width)
+            .map(|x| match columns.get(x) {
+                Some(&Column::Bar(shade)) => Some(shade),
+                _ => None
+            })
+            .collect();
+        self.push_frame(shades, height);
+
+        for (y, row) in self.viz.rows.iter_mut().enumerate() {
+            let frame = self.history.get(y);
+            for (x, val) in row.iter_mut().enumerate() {
+                let shade = frame.and_then(|frame| frame[x]).unwrap_or(0);
+                *val = SHADE_CHARS[shade] as c_char;
+            }
+        }
+
+        let colors = if self.viz.colors { RowColors::Shades(self.viz.color_scheme) } else { RowColors::Plain };
+        self.viz.put_colored_rows(bars, colors)
+    }
+}
+
+
+impl<S: Screen> Renderer for SpectrogramRenderer<S> {
+    fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        SpectrogramRenderer::render_channels(self, channels)
+    }
+}
+
+
+/// A frame handed to a CapturingRenderer
+#[derive(Clone, PartialEq, Debug)]
+pub struct CapturedFrame {
+    /// The spectrum of each channel as it was given
+    pub channels: Vec<Vec<f64>>,
+    /// The height of each channel's bars, as the visualizer would draw them
+    pub bars: Vec<Vec<usize>>
+}
+
+
+/// Keeps every frame it's given instead of drawing it, so everything up to
+/// the screen can be tested without a terminal. The bars are worked out by a
+/// Visualizer for the size of its screen, so all of its band settings apply.
+pub struct CapturingRenderer<S: Screen = Window> {
+    viz: Visualizer<S>,
+    frames: Vec<CapturedFrame>
+}
+
+
+impl<S: Screen> CapturingRenderer<S> {
+    /// Instantiate a renderer that works out its bars with viz
+    pub fn with_visualizer(viz: Visualizer<S>) -> CapturingRenderer<S> {
+        CapturingRenderer {
+            viz: viz,
+            frames: Vec::new()
+        }
+    }
+
+    /// Borrow the visualizer to change its settings
+    pub fn get_visualizer_mut(&mut self) -> &mut Visualizer<S> {
+        &mut self.viz
+    }
+
+    /// Get every frame rendered so far, oldest first
+    pub fn get_frames(&self) -> &[CapturedFrame] {
+        &self.frames[..]
+    }
+
+    /// Forget the frames rendered so far
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+
+impl<S: Screen> Renderer for CapturingRenderer<S> {
+    fn render_channels(&mut self, channels: &[&[f64]]) -> Result<(), RenderError> {
+        self.viz.update_size();
+        let (width, height) = (self.viz.width, self.viz.height);
+        let bars = try!(self.viz.compute_bar_heights(channels, width, height));
+
+        self.frames.push(CapturedFrame {
+            channels: channels.iter().map(|channel| channel.to_vec()).collect(),
+            bars: bars
+        });
+        Ok(())
+    }
+}
+
+
+#[test]
+fn test_capture_to_render() {
+    use capture::capture_ring;
+    use fftw::audio::AudioFft;
+    use fftw::synth::{sine, to_s16le};
+
+    // A tone on bin 100 of 513 lands in bar 12 of 64
+    let signal = sine(100.0 * 44100.0 / 1024.0, 0.5, 44100.0, 4096);
+    let bytes = to_s16le(&signal[..], 1);
+
+    let mut fft: AudioFft = AudioFft::new(1024, 1);
+    let window = fft.get_buf_size();
+    let (sender, receiver) = capture_ring(window * 2, fft.get_frame_size());
+    // Rows are drawn one column short, leaving 64 columns for the bars
+    let mut renderer = CapturingRenderer::with_visualizer(Visualizer::with_screen(MockScreen::new(1000, 65)));
+    renderer.get_visualizer_mut().set_bin_reduce(BinReduce::Max);
+
+    for chunk in bytes.chunks(window) {
+        sender.push(chunk);
+        let data = receiver.recv_window(window).unwrap();
+        assert_eq!(fft.feed_u8_data(&data[..]), window);
+        fft.execute();
+        fft.compute_output();
+        renderer.render_frame(fft.get_output()).unwrap();
+    }
+
+    let frames = renderer.get_frames();
+    assert_eq!(frames.len(), 4);
+    for frame in frames.iter() {
+        assert_eq!(frame.channels[0].len(), 513);
+        assert_eq!(frame.bars.len(), 1);
+        assert_eq!(frame.bars[0].len(), 64);
+        let loudest = (0..64).fold(0, |max, i| if frame.bars[0][i] > frame.bars[0][max] { i } else { max });
+        assert_eq!(loudest, 12);
+    }
+
+    assert_eq!(renderer.render_channels(&[]), Err(RenderError::ChannelCount(0)));
+    assert_eq!(renderer.get_frames().len(), 4);
+}
+
+
+#[test]
+fn test_fit_debug_info_narrow_window() {
+    let debuginfo = " width: 5, height: 10, bars: 5 ";
+    assert_eq!(fit_debug_info(debuginfo, 5), (0, " widt"));
+    assert_eq!(fit_debug_info(debuginfo, 0), (0, ""));
+    assert_eq!(fit_debug_info(debuginfo, 100), (100 - debuginfo.len(), debuginfo));
+}
+
+
+#[test]
+fn test_scale_fft_output_log() {
+    // Far more bands than bins: the bass bands hold no bins of their own
+    let spectrum: Vec<f64> = (0..16).map(|i| 10.0 + i as f64).collect();
+    let empty = scale_fft_output_log(&spectrum[..], 0, 32, BinReduce::Average, false);
+    assert_eq!(empty.len(), 32);
+    assert!(empty.iter().any(|&x| x == 0.0));
+
+    // Filling them leaves every band with a bin, in order from bass to treble
+    let filled = scale_fft_output_log(&spectrum[..], 0, 32, BinReduce::Average, true);
+    assert_eq!(filled.len(), 32);
+    assert!(filled.iter().all(|&x| x.is_finite() && x >= 11.0));
+    for i in 1..filled.len() {
+        assert!(filled[i] >= filled[i - 1]);
+    }
+    assert_eq!(filled[31], (24.0 + 25.0) / 2.0);
+
+    // With fewer bands than bins every bin from low_bin up is used once
+    let sums = scale_fft_output_log(&[1.0; 64], 4, 4, BinReduce::Sum, true);
+    assert_eq!(sums.iter().fold(0.0, |sum, &x| sum + x), 60.0);
+    assert_eq!(sums, vec![4.0, 8.0, 16.0, 32.0]);
+}
+
+
+#[test]
+fn test_scale_fft_output_custom() {
+    // 9 bins from 0 Hz to 4000 Hz, 500 Hz apart, with tones at 1000 and 3000
+    let spectrum = [0.0, 0.0, 8.0, 0.0, 0.0, 0.0, 6.0, 0.0, 0.0];
+    let edges = [0.0, 750.0, 1250.0, 2750.0, 3250.0, 4000.0];
+    let bands = scale_fft_output_custom(&spectrum, &edges, 8000.0, BinReduce::Max).unwrap();
+    assert_eq!(bands, vec![0.0, 8.0, 0.0, 6.0, 0.0]);
+
+    // A band between two bins is interpolated from them
+    let bands = scale_fft_output_custom(&spectrum, &[1100.0, 1300.0], 8000.0, BinReduce::Max).unwrap();
+    assert!((bands[0] - 4.8).abs() < 1e-9);
+
+    assert_eq!(scale_fft_output_custom(&spectrum, &[100.0], 8000.0, BinReduce::Max),
+               Err(BandEdgeError::TooFewEdges(1)));
+    assert_eq!(scale_fft_output_custom(&spectrum, &[0.0, 500.0, 500.0], 8000.0, BinReduce::Max),
+               Err(BandEdgeError::Unsorted(2)));
+    assert_eq!(scale_fft_output_custom(&spectrum, &[0.0, 5000.0], 8000.0, BinReduce::Max),
+               Err(BandEdgeError::OutOfRange(5000.0)));
+}
+
+
+#[test]
+fn test_freq_range() {
+    // 9 bins from 0 Hz to 4000 Hz, 500 Hz apart
+    assert_eq!(freq_range_bins(9, 1000.0, 3000.0, 8000.0), (2, 7));
+    assert_eq!(freq_range_bins(9, 900.0, 3100.0, 8000.0), (2, 7));
+    assert_eq!(freq_range_bins(9, 0.0, 20000.0, 8000.0), (0, 9));
+    // A range between two bins still gets one
+    assert_eq!(freq_range_bins(9, 1100.0, 1200.0, 8000.0), (3, 4));
+
+    // Loud bins outside the range are left out, so the loudest bin in it
+    // reaches the top
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 6));
+    viz.set_sample_rate(8000.0);
+    viz.set_freq_range(1000.0, 3000.0);
+    let spectrum = [100.0, 100.0, 1.0, 2.0, 3.0, 4.0, 5.0, 100.0, 100.0];
+    let (_, heights) = viz.compute_bands(&[&spectrum[..]], 5, 10, &[]).unwrap();
+    assert_eq!(heights, vec![vec![2, 4, 5, 7, 9]]);
+}
+
+
+#[test]
+fn test_fold_column() {
+    // An odd width has a single middle column
+    let folded: Vec<usize> = (0..7).map(|x| fold_column(x, 7)).collect();
+    assert_eq!(folded, vec![3, 2, 1, 0, 1, 2, 3]);
+    // An even width shares the middle between two columns
+    let folded: Vec<usize> = (0..6).map(|x| fold_column(x, 6)).collect();
+    assert_eq!(folded, vec![2, 1, 0, 0, 1, 2]);
+
+    for width in 1..20 {
+        for x in 0..width {
+            assert_eq!(fold_column(x, width), fold_column(width - 1 - x, width));
+            assert!(fold_column(x, width) < width - width / 2);
+        }
+    }
+
+    // The bass bar ends up in the middle of the screen
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 8));
+    viz.set_layout(Layout::MonoMirror);
+    viz.render_frame(&[8.0, 4.0, 2.0, 1.0]).unwrap();
+    assert_eq!(viz.bar_heights, vec![9, 5, 2, 1]);
+    let tops: Vec<c_char> = viz.rows[9].clone();
+    let expected: Vec<c_char> = [EMPTY_CHAR, EMPTY_CHAR, EMPTY_CHAR, BAR_CHAR, EMPTY_CHAR, EMPTY_CHAR, EMPTY_CHAR].to_vec();
+    assert_eq!(tops, expected);
+}
+
+
+#[test]
+fn test_include_dc() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 3));
+    viz.set_bin_reduce(BinReduce::Max);
+    let mut spectrum = [1.0; 9];
+    spectrum[0] = 100.0;
+    spectrum[8] = 50.0;
+
+    // The DC bin dominates the first band until it's left out, and the
+    // Nyquist bin always makes it into the last band
+    let (_, heights) = viz.compute_bands(&[&spectrum[..]], 2, 10, &[]).unwrap();
+    assert_eq!(heights, vec![vec![9, 5]]);
+    viz.set_include_dc(false);
+    let (_, heights) = viz.compute_bands(&[&spectrum[..]], 2, 10, &[]).unwrap();
+    assert_eq!(heights, vec![vec![0, 9]]);
+}
+
+
+#[test]
+fn test_band_to_frequency() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 5));
+
+    // 513 bins 1 Hz apart split into 4 linear bands of 128 or 129 bins
+    assert_eq!(viz.band_to_frequency(0, 4, 513, 1024.0), (0.0, 127.5));
+    assert_eq!(viz.band_to_frequency(1, 4, 513, 1024.0), (127.5, 255.5));
+    assert_eq!(viz.band_to_frequency(3, 4, 513, 1024.0), (383.5, 512.0));
+    // Twice the sample rate puts each bin twice as high
+    assert_eq!(viz.band_to_frequency(3, 4, 513, 2048.0), (767.0, 1024.0));
+
+    // Log bands start after DC and grow by 513^(1/4) each
+    viz.set_band_spacing(BandSpacing::Logarithmic);
+    assert_eq!(viz.band_to_frequency(0, 4, 513, 1024.0), (0.5, 3.5));
+    assert_eq!(viz.band_to_frequency(3, 4, 513, 1024.0), (106.5, 512.0));
+
+    // Custom bands are their own edges
+    viz.set_band_edges(&[20.0, 100.0, 400.0, 500.0]).unwrap();
+    assert_eq!(viz.band_to_frequency(0, 3, 513, 1024.0), (20.0, 100.0));
+    assert_eq!(viz.band_to_frequency(2, 3, 513, 1024.0), (400.0, 500.0));
+    viz.clear_band_edges();
+
+    // The frequency range is taken into account
+    viz.set_freq_range(100.0, 300.0);
+    viz.set_band_spacing(BandSpacing::Linear);
+    assert_eq!(viz.band_to_frequency(0, 2, 513, 1024.0), (99.5, 199.5));
+    assert_eq!(viz.band_to_frequency(1, 2, 513, 1024.0), (199.5, 300.5));
+}
+
+
+#[test]
+fn test_band_edges() {
+    // A tone at 450 Hz only shows up in the last of three custom bands
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 13));
+    viz.set_sample_rate(1024.0);
+    viz.set_band_edges(&[0.0, 100.0, 400.0, 512.0]).unwrap();
+    viz.set_bin_reduce(BinReduce::Max);
+    let mut data = vec![0.0; 513];
+    data[450] = 100.0;
+    assert_eq!(viz.compute_bar_heights(&[&data[..]], 12, 10), Ok(vec![vec![0, 0, 9]]));
+    viz.render_frame(&data[..]).unwrap();
+    assert_eq!(viz.get_band_count(), 3);
+
+    // Edges past the Nyquist frequency are refused, and stop a frame from
+    // being drawn if the sample rate drops under them
+    assert_eq!(viz.set_band_edges(&[0.0, 600.0]), Err(BandEdgeError::OutOfRange(600.0)));
+    viz.set_sample_rate(800.0);
+    assert_eq!(viz.render_frame(&data[..]), Err(RenderError::BandEdges(BandEdgeError::OutOfRange(512.0))));
+}
+
+
+#[test]
+fn test_column_fill() {
+    let border = '.' as c_char;
+    assert_eq!(column_fill(None, Layout::Bars, 10, border, false).cell(0), border);
+    assert_eq!(column_fill(None, Layout::Bars, 10, border, true).cell(0), INIT_CHAR);
+    assert_eq!(column_fill(Some(&Column::Border), Layout::Bars, 10, border, true).cell(0), border);
+    assert_eq!(column_fill(Some(&Column::Gap), Layout::Bars, 10, border, false).cell(0), EMPTY_CHAR);
+    // A bar 3 rows tall covers rows 0 to 3
+    assert_eq!(column_fill(Some(&Column::Bar(3)), Layout::Bars, 10, border, false).cell(3), BAR_CHAR);
+    assert_eq!(column_fill(Some(&Column::Bar(3)), Layout::Bars, 10, border, false).cell(4), EMPTY_CHAR);
+    // A peak above the bar is marked at its top, and hidden inside the bar
+    let fill = column_fill(Some(&Column::Bar(3)), Layout::Bars, 10, border, false)
+        .with_peak(Some(&Column::Bar(6)), Layout::Bars, 10);
+    assert_eq!(fill.cell(6), PEAK_CHAR);
+    assert_eq!(fill.cell(5), EMPTY_CHAR);
+    assert_eq!(fill.cell(0), BAR_CHAR);
+}
+
+
+#[test]
+fn test_clip_hold() {
+    // A single bar on a dB range topping out at 0 dB
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 2));
+    viz.set_db_range(-90.0, 0.0);
+    viz.set_clip_hold(Some(2));
+    let marked = |viz: &Visualizer<MockScreen>| viz.rows.iter().any(|row| row[0] == CLIP_CHAR);
+
+    // A full scale tone marks the top of the bar
+    viz.render_frame(&[0.0]).unwrap();
+    assert_eq!(viz.rows[9][0], CLIP_CHAR);
+    assert_eq!(viz.rows[8][0], BAR_CHAR);
+
+    // The mark follows the tip of the bar down for two frames once the level
+    // drops, and then clears
+    viz.render_frame(&[-50.0]).unwrap();
+    assert_eq!(viz.rows[4][0], CLIP_CHAR);
+    viz.render_frame(&[-50.0]).unwrap();
+    assert!(marked(&viz));
+    viz.render_frame(&[-50.0]).unwrap();
+    assert!(!marked(&viz));
+    viz.render_frame(&[-20.0]).unwrap();
+    assert!(!marked(&viz));
+}
+
+
+#[test]
+fn test_peak_lags_falling_bar() {
+    // A single bar whose height is its level in dB
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 2));
+    viz.set_db_range(0.0, 9.0);
+    viz.set_peak_hold(Some(1.0));
+
+    // The peak follows the bar straight up, then falls a row a frame behind it
+    let mut peaks = Vec::new();
+    for &level in [2.0, 6.0, 6.0, 3.0, 1.0, 1.0].iter() {
+        viz.render_frame(&[level]).unwrap();
+        peaks.push(viz.peaks[0].value);
+    }
+    assert_eq!(peaks, vec![2.0, 6.0, 6.0, 5.0, 4.0, 3.0]);
+
+    // Hiding the peaks forgets them
+    viz.set_peak_hold(None);
+    viz.render_frame(&[1.0]).unwrap();
+    assert!(viz.peaks.is_empty());
+}
+
+
+#[test]
+fn test_band_column_ranges() {
+    // 16 bands over 80 columns: blocks of 4 or 5 columns with 1 column gaps
+    let ranges = band_column_ranges(16, 80, 1);
+    assert_eq!(ranges.len(), 16);
+    assert_eq!(ranges[0].0, 0);
+    assert_eq!(ranges[15].1, 80);
+    for i in 1..ranges.len() {
+        assert_eq!(ranges[i].0, ranges[i - 1].1 + 1);
+        assert!(ranges[i].1 > ranges[i].0);
+    }
+
+    // Too narrow for gaps: every column is used by exactly one band
+    assert_eq!(band_column_ranges(3, 4, 1), vec![(0, 1), (1, 2), (2, 4)]);
+    // More bands than columns
+    assert_eq!(band_column_ranges(10, 2, 1), vec![(0, 1), (1, 2)]);
+    assert_eq!(band_column_ranges(0, 10, 1), vec![]);
+    // Wider gaps
+    assert_eq!(band_column_ranges(3, 7, 2), vec![(0, 1), (3, 4), (6, 7)]);
+}
+
+
+#[test]
+fn test_layout_columns() {
+    assert_eq!(layout_columns(&[3, 1], 5, true, 1),
+               vec![Column::Bar(3), Column::Bar(3), Column::Gap, Column::Bar(1), Column::Bar(1)]);
+    assert_eq!(layout_columns(&[3, 1], 3, false, 0),
+               vec![Column::Bar(3), Column::Bar(1), Column::Border]);
+}
+
+
+#[test]
+fn test_bar_count() {
+    assert_eq!(bar_count(80, 0), 80);
+    assert_eq!(bar_count(80, 1), 40);
+    assert_eq!(bar_count(79, 1), 40);
+    assert_eq!(bar_count(7, 2), 3);
+    // A gap wider than the window still leaves one bar
+    assert_eq!(bar_count(5, 100), 1);
+    assert_eq!(bar_count(0, 0), 0);
+    assert_eq!(bar_count(0, 3), 0);
+}
+
+
+#[test]
+fn test_stereo_widths() {
+    assert_eq!(stereo_widths(81), (40, 40));
+    assert_eq!(stereo_widths(80), (40, 39));
+    assert_eq!(stereo_widths(1), (0, 0));
+    assert_eq!(stereo_widths(0), (0, 0));
+}
+
+
+#[test]
+fn test_mirror_rows_odd_height() {
+    // The middle row of 7 is row 3
+    assert_eq!(mirror_rows(1, 7), (3, 4));
+    assert_eq!(mirror_rows(3, 7), (2, 5));
+    assert_eq!(mirror_rows(7, 7), (0, 7));
+    assert_eq!(mirror_rows(20, 7), (0, 7));
+}
+
+
+#[test]
+fn test_mirror_rows_even_height() {
+    // The middle of 6 rows falls between rows 2 and 3
+    assert_eq!(mirror_rows(1, 6), (2, 4));
+    assert_eq!(mirror_rows(3, 6), (1, 5));
+    assert_eq!(mirror_rows(6, 6), (0, 6));
+    assert_eq!(mirror_rows(20, 6), (0, 6));
+}
+
+
+#[test]
+fn test_bar_rows_orientation() {
+    // A bar 3 rows tall in a 10 row window, counted from the bottom
+    assert_eq!(bar_rows(Layout::Bars, 2, 10), (0, 3));
+    assert_eq!(bar_rows(Layout::TopDown, 2, 10), (7, 10));
+    // Row 9 is the top of the window
+    assert_eq!(column_fill(Some(&Column::Bar(2)), Layout::TopDown, 10, BORDER_CHAR, false).cell(9), BAR_CHAR);
+    assert_eq!(column_fill(Some(&Column::Bar(2)), Layout::TopDown, 10, BORDER_CHAR, false).cell(6), EMPTY_CHAR);
+    assert_eq!(column_fill(Some(&Column::Bar(2)), Layout::Bars, 10, BORDER_CHAR, false).cell(0), BAR_CHAR);
+    assert_eq!(bar_rows(Layout::TopDown, 9, 10), (0, 10));
+}
+
+
+#[test]
+fn test_spectrogram_scrolls() {
+    let mut spectrogram = SpectrogramRenderer::with_visualizer(Visualizer::with_screen(MockScreen::new(3, 5)));
+
+    let frames = [[100.0, 0.0, 0.0, 0.0], [0.0, 100.0, 0.0, 0.0], [0.0, 0.0, 100.0, 0.0], [0.0, 0.0, 0.0, 100.0]];
+    for frame in frames.iter() {
+        spectrogram.render_frame(&frame[..]).unwrap();
+    }
+
+    // Only the last 3 frames fit, newest on the bottom row
+    let loudest = SHADE_CHARS[SHADE_CHARS.len() - 1] as c_char;
+    let rows = &spectrogram.viz.rows;
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0][3], loudest);
+    assert_eq!(rows[1][2], loudest);
+    assert_eq!(rows[2][1], loudest);
+    assert_eq!(rows[2][0], ' ' as c_char);
+    assert_eq!(spectrogram.history.len(), 3);
+    // Without color support everything is in the terminal's own color
+    assert!(spectrogram.viz.win.cell_pairs.values().all(|&pair| pair == 0));
+
+    // With color, the loud cells are red and the quiet ones green. Screen
+    // rows are counted from the top, so row 0 of the history is screen row 2.
+    spectrogram.viz.colors = true;
+    spectrogram.viz.set_color_scheme(ColorScheme::Amplitude);
+    spectrogram.render_frame(&frames[0][..]).unwrap();
+    let pairs = &spectrogram.viz.win.cell_pairs;
+    let red = color::level_pair(ColorScheme::Amplitude, SHADE_CHARS.len() - 1, SHADE_CHARS.len());
+    let green = color::level_pair(ColorScheme::Amplitude, 0, SHADE_CHARS.len());
+    assert_eq!(pairs[&(2, 0)], red);
+    assert_eq!(pairs[&(1, 3)], red);
+    assert_eq!(pairs[&(0, 2)], red);
+    assert_eq!(pairs[&(2, 1)], green);
+}
+
+
+#[test]
+fn test_render_matches_per_cell_bars() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(7, 13));
+    viz.set_layout(Layout::Mirror);
+    let data: Vec<f64> = (0..12).map(|x| (x * 37 % 11) as f64 * 10.0).collect();
+    viz.render_frame(&data[..]).unwrap();
+
+    // Work out every cell on its own, the slow way
+    let (columns, _) = viz.compute_bands(&[&data[..]], 12, 7, &[]).unwrap();
+    for y in 0..7 {
+        for x in 0..12 {
+            let expected = match columns[x] {
+                Column::Bar(bar) => {
+                    let (start, end) = bar_rows(Layout::Mirror, bar, 7);
+                    if y >= start && y < end { BAR_CHAR } else { EMPTY_CHAR }
+                },
+                Column::Gap => EMPTY_CHAR,
+                Column::Border => BORDER_CHAR
+            };
+            assert_eq!(viz.rows[y][x], expected);
+        }
+    }
+}
+
+
+/// A screen of a fixed size for testing the visualizer without a terminal
+#[cfg(test)]
+struct MockScreen {
+    max_y: c_int,
+    max_x: c_int,
+    // The number of rows drawn with addbytes
+    rows_drawn: usize,
+    // Keys waiting to be read by getch
+    keys: Vec<c_int>,
+    // The color pair set with color_set
+    pair: c_short,
+    // The color pair each cell drawn with addbytes was last drawn in, by
+    // (y, x)
+    cell_pairs: ::std::collections::HashMap<(c_int, c_int), c_short>
+}
+
+
+#[cfg(test)]
+impl MockScreen {
+    fn new(max_y: c_int, max_x: c_int) -> MockScreen {
+        MockScreen { max_y: max_y, max_x: max_x, rows_drawn: 0, keys: Vec::new(), pair: 0, cell_pairs: ::std::collections::HashMap::new() }
+    }
+}
+
+
+#[cfg(test)]
+impl Screen for MockScreen {
+    fn addstr(&mut self, _: c_int, _: c_int, _: &str) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn addbytes(&mut self, y: c_int, x: c_int, bytes: &[c_char]) -> Result<c_int, c_int> {
+        self.rows_drawn += 1;
+        for i in 0..bytes.len() {
+            self.cell_pairs.insert((y, x + i as c_int), self.pair);
+        }
+        Ok(0)
+    }
+
+    fn noutrefresh(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn doupdate(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn get_max_yx(&self) -> Result<(c_int, c_int), c_int> {
+        Ok((self.max_y, self.max_x))
+    }
+
+    fn getch(&mut self) -> Option<c_int> {
+        if self.keys.is_empty() {
+            None
+        } else {
+            Some(self.keys.remove(0))
+        }
+    }
+
+    fn color_set(&mut self, pair: c_short) -> Result<c_int, c_int> {
+        self.pair = pair;
+        Ok(0)
+    }
+}
+
+
+#[test]
+fn test_render_to_image() {
+    let viz = Visualizer::with_screen(MockScreen::new(0, 0));
+    let mut data = vec![0.0; 8];
+    data[2] = 100.0;
+    data[5] = 50.0;
+
+    let image = viz.render_to_image(&[&data[..]], 8, 5).unwrap();
+    let white = ColorScheme::Monochrome.get_color(0, 5, 0, 8);
+    let black = (0, 0, 0);
+
+    // The spike fills the whole column
+    for y in 0..5 {
+        assert_eq!(image.get_pixel(2, y), white);
+    }
+    // Half the spike's height fills rows 2..5 of 0..4 (counted from the top)
+    assert_eq!(image.get_pixel(5, 1), black);
+    assert_eq!(image.get_pixel(5, 2), white);
+    assert_eq!(image.get_pixel(5, 4), white);
+    // Quiet bars still get their bottom row drawn
+    assert_eq!(image.get_pixel(0, 4), white);
+    assert_eq!(image.get_pixel(0, 3), black);
+}
+
+
+#[test]
+fn test_bar_level() {
+    // With a max of 10 on an 11 row window, every unit of x is one row
+    assert_eq!(bar_level(2.5, 10.0, 11).round(), 3.0);
+    assert_eq!(bar_level(2.4, 10.0, 11).round(), 2.0);
+    assert_eq!(bar_level(10.0, 10.0, 11).round(), 10.0);
+    assert_eq!(bar_level(0.5, 10.0, 11).round(), 0.0);
+    // Clamped to the top row
+    assert_eq!(bar_level(20.0, 10.0, 11).round(), 10.0);
+    assert_eq!(bar_level(5.0, 10.0, 0).round(), 0.0);
+}
+
+
+#[test]
+fn test_hysteresis_height() {
+    // A level wobbling by 0.3 rows around the drawn height stays put
+    let mut drawn = 5;
+    for &level in [5.3, 4.7, 5.3, 4.7].iter() {
+        drawn = hysteresis_height(level, drawn, 0.4);
+        assert_eq!(drawn, 5);
+    }
+    // A bigger move goes to the nearest row
+    assert_eq!(hysteresis_height(5.6, 5, 0.4), 6);
+    assert_eq!(hysteresis_height(3.2, 5, 0.4), 3);
+}
+
+
+#[test]
+fn test_db_bar_level() {
+    // Halfway between the floor and ceiling is halfway up the window
+    assert_eq!(db_bar_level(-40.0, -80.0, 0.0, 11).round(), 5.0);
+    assert_eq!(db_bar_level(0.0, -80.0, 0.0, 11).round(), 10.0);
+    // Levels outside the range are clamped to it
+    assert_eq!(db_bar_level(-120.0, -80.0, 0.0, 11).round(), 0.0);
+    assert_eq!(db_bar_level(6.0, -80.0, 0.0, 11).round(), 10.0);
+    assert_eq!(db_bar_level(::std::f64::NEG_INFINITY, -80.0, 0.0, 11).round(), 0.0);
+}
+
+
+#[test]
+fn test_log_bar_level() {
+    // The geometric midpoint of the range is halfway up the window
+    assert_eq!(log_bar_level(100.0, 1.0, 10000.0, 11).round(), 5.0);
+    assert_eq!(log_bar_level(10000.0, 1.0, 10000.0, 11).round(), 10.0);
+    // Magnitudes at or below the floor, including silence, are on the bottom
+    assert_eq!(log_bar_level(1.0, 1.0, 10000.0, 11), 0.0);
+    assert_eq!(log_bar_level(0.0, 1.0, 10000.0, 11), 0.0);
+    assert_eq!(log_bar_level(1e9, 1.0, 10000.0, 11), 10.0);
+    // A floor of zero has no place on a log scale
+    assert_eq!(log_bar_level(100.0, 0.0, 10000.0, 11), 0.0);
+}
+
+
+#[test]
+fn test_silence_latch() {
+    // Goes idle below the threshold, and only wakes up well above it
+    assert!(silence_latch(false, -61.0, -60.0));
+    assert!(!silence_latch(false, -59.0, -60.0));
+    assert!(silence_latch(true, -59.0, -60.0));
+    assert!(!silence_latch(true, -56.0, -60.0));
+}
+
+
+#[test]
+fn test_silence_goes_idle() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 9));
+    viz.set_silence_threshold(-60.0);
+
+    // Audio at the dB floor is silent, and draws flat bars rather than
+    // scaling the noise up to the top
+    let mut silence = vec![-120.0; 8];
+    silence[3] = -119.0;
+    viz.render_frame(&silence[..]).unwrap();
+    assert!(viz.is_idle());
+    assert!(viz.bar_heights.iter().all(|&height| height == 0));
+
+    // Just above the threshold isn't enough to wake it up
+    viz.render_frame(&[-59.0; 8]).unwrap();
+    assert!(viz.is_idle());
+    viz.render_frame(&[-20.0; 8]).unwrap();
+    assert!(!viz.is_idle());
+}
+
+
+#[test]
+fn test_sanitize_spectrum() {
+    use std::f64::{INFINITY, NAN, NEG_INFINITY};
+
+    let mut spectrum = [-10.0, NAN, INFINITY, -30.0, NEG_INFINITY];
+    assert_eq!(sanitize_spectrum(&mut spectrum, -120.0), 3);
+    assert_eq!(spectrum, [-10.0, -120.0, -120.0, -30.0, -120.0]);
+
+    // The bad bins are drawn as silence and the rest are unaffected
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 7));
+    let clean = [2.0, 0.0, 4.0, 0.0, 8.0, 6.0];
+    viz.render_frame(&clean).unwrap();
+    let expected = viz.bar_heights.clone();
+    viz.render_frame(&[2.0, NAN, 4.0, INFINITY, 8.0, 6.0]).unwrap();
+    assert_eq!(viz.bar_heights, expected);
+    assert_eq!(viz.get_scrubbed_count(), 2);
+    assert!(viz.last_frame[0].iter().all(|x| x.is_finite()));
+
+    // The spectrogram scrubs its frames too
+    let mut spectrogram = SpectrogramRenderer::with_visualizer(Visualizer::with_screen(MockScreen::new(3, 5)));
+    spectrogram.render_frame(&[100.0, 0.0, 0.0, 0.0]).unwrap();
+    let expected = spectrogram.history[0].clone();
+    spectrogram.render_frame(&[100.0, NAN, 0.0, NEG_INFINITY]).unwrap();
+    assert_eq!(spectrogram.history[0], expected);
+    assert_eq!(spectrogram.viz.get_scrubbed_count(), 2);
+}
+
+
+#[test]
+fn test_scrubbed_silence_stays_idle() {
+    use std::f64::NAN;
+
+    // A bad bin in a silent dB frame becomes the dB floor, not 0 dB, so the
+    // display doesn't wake up
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 9));
+    viz.set_silence_threshold(-60.0);
+    viz.render_frame(&[-100.0; 8]).unwrap();
+    assert!(viz.is_idle());
+    let mut frame = vec![-100.0; 8];
+    frame[3] = NAN;
+    viz.render_frame(&frame[..]).unwrap();
+    assert!(viz.is_idle());
+    assert_eq!(viz.last_frame[0][3], DEFAULT_DB_FLOOR);
+
+    // Linear output is silent at 0
+    viz.set_silence_level(0.0);
+    viz.render_frame(&frame[..]).unwrap();
+    assert_eq!(viz.last_frame[0][3], 0.0);
+}
+
+
+#[test]
+fn test_smooth_spectrum() {
+    let spike = [0.0, 0.0, 0.0, 9.0, 0.0, 0.0, 0.0];
+    assert_eq!(smooth_spectrum(&spike, 3), vec![0.0, 0.0, 3.0, 3.0, 3.0, 0.0, 0.0]);
+    assert_eq!(smooth_spectrum(&spike, 1), spike.to_vec());
+
+    // The window shrinks at the edges
+    assert_eq!(smooth_spectrum(&[6.0, 0.0, 0.0, 0.0], 3), vec![3.0, 2.0, 0.0, 0.0]);
+    // Even windows lean towards the higher bins
+    assert_eq!(smooth_spectrum(&[0.0, 8.0, 0.0, 0.0], 4), vec![8.0 / 3.0, 2.0, 8.0 / 3.0, 0.0]);
+}
+
+
+#[test]
+fn test_scale_fft_output() {
+    assert_eq!(scale_fft_output(&[1.0, 2.0, 3.0, 4.0], 2, BinReduce::Average), vec![1.5, 3.5]);
+    assert_eq!(scale_fft_output(&[1.0, 2.0, 3.0, 4.0, 5.0], 2, BinReduce::Average), vec![1.5, 4.0]);
+    assert_eq!(scale_fft_output(&[1.0, 2.0, 3.0, 4.0], 2, BinReduce::Sum), vec![3.0, 7.0]);
+    assert_eq!(scale_fft_output(&[1.0, 2.0], 4, BinReduce::Average), vec![1.0, 2.0]);
+
+    // Every band is produced, even when it doesn't divide evenly
+    let input: Vec<f64> = (0..513).map(|x| x as f64).collect();
+    assert_eq!(scale_fft_output(&input[..], 200, BinReduce::Average).len(), 200);
+}
+
+
+#[test]
+fn test_max_binning_preserves_peaks() {
+    let mut input = vec![0.0; 64];
+    input[21] = 80.0;
+
+    let average = scale_fft_output(&input[..], 8, BinReduce::Average);
+    let max = scale_fft_output(&input[..], 8, BinReduce::Max);
+    assert_eq!(average[2], 10.0);
+    assert_eq!(max[2], 80.0);
+    assert_eq!(max[3], 0.0);
+}
+
+
+#[test]
+fn test_energy_binning() {
+    // Two bands of the same level, but the second is one bin wider
+    let input = [60.0; 5];
+    let average = scale_fft_output(&input, 2, BinReduce::Average);
+    let energy = scale_fft_output(&input, 2, BinReduce::Energy);
+    assert_eq!(average, vec![60.0, 60.0]);
+
+    // Doubling the power is 3dB and tripling it is 4.8dB
+    assert!((energy[0] - 63.01).abs() < 0.01);
+    assert!((energy[1] - 64.77).abs() < 0.01);
+}
+
+
+#[test]
+fn test_only_changed_rows_are_drawn() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 9));
+    let mut data = vec![0.0; 8];
+    data[3] = 100.0;
+
+    viz.render_frame(&data[..]).unwrap();
+    assert_eq!(viz.win.rows_drawn, 10);
+
+    // Nothing changed
+    viz.render_frame(&data[..]).unwrap();
+    assert_eq!(viz.win.rows_drawn, 10);
+
+    // The spike moves, but the bottom row is full either way
+    data[3] = 0.0;
+    data[4] = 100.0;
+    viz.render_frame(&data[..]).unwrap();
+    assert_eq!(viz.win.rows_drawn, 19);
+
+    // A resize redraws everything
+    viz.win.max_y = 12;
+    viz.render_frame(&data[..]).unwrap();
+    assert_eq!(viz.win.rows_drawn, 31);
+}
+
+
+#[test]
+fn test_resize_recomputes_bands() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(20, 81));
+    viz.render_frame(&[0.0; 512]).unwrap();
+    assert_eq!(viz.get_band_count(), 80);
+
+    // The resize is picked up as soon as the event is read, before the next
+    // frame is drawn
+    viz.win.max_x = 41;
+    viz.win.keys = vec![KEY_RESIZE, 'q' as c_int];
+    assert_eq!(viz.read_key(), Some('q' as c_int));
+    assert_eq!(viz.get_band_count(), 40);
+    assert_eq!(viz.read_key(), None);
+
+    viz.set_layout(Layout::StereoSplit);
+    assert_eq!(viz.get_band_count(), 39);
+}
+
+
+#[test]
+fn test_width_and_height() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(24, 80));
+    assert_eq!(viz.get_width(), 79);
+    assert_eq!(viz.get_height(), 24);
+
+    // Drawing uses the same size the accessors report
+    viz.render_frame(&[1.0; 100]).unwrap();
+    assert_eq!(viz.rows.len(), viz.get_height());
+    assert_eq!(viz.rows[0].len(), viz.get_width());
+}
+
+
+#[test]
+fn test_zero_sized_window() {
+    for &(max_y, max_x) in [(0, 0), (24, 0), (24, 1), (0, 80), (-1, -1)].iter() {
+        let mut viz = Visualizer::with_screen(MockScreen::new(max_y, max_x));
+        assert_eq!(viz.render_frame(&[10.0; 64]), Ok(()));
+        assert_eq!(viz.win.rows_drawn, 0);
+    }
+
+    let viz = Visualizer::with_screen(MockScreen::new(24, 0));
+    assert_eq!(viz.get_width(), 0);
+    assert_eq!(viz.get_height(), 24);
+}
+
+
+#[test]
+fn test_tiny_window_with_debug_overlay() {
+    // One drawable column is far narrower than the debug info
+    let mut viz = Visualizer::with_screen(MockScreen::new(24, 2));
+    viz.set_debug_overlay(true);
+    assert_eq!(viz.get_width(), 1);
+    assert_eq!(viz.render_frame(&[10.0; 64]), Ok(()));
+    assert_eq!(viz.win.rows_drawn, 24);
+
+    // No rows at all draws nothing
+    let mut viz = Visualizer::with_screen(MockScreen::new(0, 80));
+    viz.set_debug_overlay(true);
+    assert_eq!(viz.render_frame(&[10.0; 64]), Ok(()));
+    assert_eq!(viz.win.rows_drawn, 0);
+
+    for &(max_y, max_x) in [(24, 2), (0, 80)].iter() {
+        let mut spectrogram = SpectrogramRenderer::with_visualizer(Visualizer::with_screen(MockScreen::new(max_y, max_x)));
+        spectrogram.get_visualizer_mut().set_debug_overlay(true);
+        assert_eq!(spectrogram.render_frame(&[10.0; 64]), Ok(()));
+    }
+}
+
+
+#[test]
+fn test_scale_fft_output_to_nothing() {
+    assert_eq!(scale_fft_output(&[1.0, 2.0, 3.0], 0, BinReduce::Average), Vec::<f64>::new());
+    assert_eq!(scale_fft_output(&[], 0, BinReduce::Max), Vec::<f64>::new());
+}
+
+
+#[test]
+fn test_paused_keeps_last_frame() {
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 9));
+    let mut first = vec![0.0; 8];
+    first[2] = 100.0;
+    viz.render_frame(&first[..]).unwrap();
+    let drawn = viz.rows.clone();
+
+    viz.set_paused(true);
+    let mut second = vec![0.0; 8];
+    second[6] = 100.0;
+    viz.render_frame(&second[..]).unwrap();
+    assert_eq!(viz.last_frame, vec![first.clone()]);
+    assert_eq!(viz.rows, drawn);
+
+    // A resize while paused still shows the frozen frame
+    viz.win.max_y = 6;
+    viz.redraw().unwrap();
+    assert_eq!(viz.rows.len(), 6);
+    assert_eq!(viz.rows[5][2], BAR_CHAR);
+    assert_eq!(viz.rows[5][6], EMPTY_CHAR);
+
+    viz.set_paused(false);
+    viz.render_frame(&second[..]).unwrap();
+    assert_eq!(viz.last_frame, vec![second]);
+}
+
+
+#[test]
+fn test_paused_holds_markers() {
+    // Two bars on a dB range, both falling away from the top
+    let mut viz = Visualizer::with_screen(MockScreen::new(10, 3));
+    viz.set_db_range(0.0, 9.0);
+    viz.set_peak_hold(Some(1.0));
+    viz.set_clip_hold(Some(2));
+    viz.render_frame(&[9.0, 8.0]).unwrap();
+    viz.render_frame(&[2.0, 1.0]).unwrap();
+    let drawn = viz.rows.clone();
+    assert!(drawn.iter().any(|row| row.contains(&CLIP_CHAR)));
+    assert!(drawn.iter().any(|row| row.contains(&PEAK_CHAR)));
+
+    // Redrawing the frozen frame doesn't let the markers fall or the clip
+    // marks run out
+    let (peaks, counters) = (viz.peaks.clone(), viz.clip_counters.clone());
+    viz.set_paused(true);
+    for _ in 0..5 {
+        viz.redraw().unwrap();
+    }
+    assert_eq!(viz.peaks, peaks);
+    assert_eq!(viz.clip_counters, counters);
+    assert_eq!(viz.rows, drawn);
+}
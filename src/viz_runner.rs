@@ -1,13 +1,22 @@
 extern crate libc;
 
 use self::libc::{c_int, size_t};
+#[cfg(test)]
+use self::libc::{c_char, c_short};
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::sync::mpsc;
+use std::thread;
 
-use fftw::audio::AudioFft;
+use capture::{self, CaptureReceiver, CaptureSender};
+use config::{self, VisualizerConfig};
+use controls::{self, Action};
+use fftw::audio::{AudioFft, FftError};
+use frame_limiter::FrameLimiter;
+use ncurses::window::{Screen, Window};
 use pulse::{Context, PulseAudioMainloop, PulseAudioStream};
 use pulse::types::*;
-use visualizer;
+use visualizer::{self, Layout, RenderError};
 
 
 const DEFAULT_SAMPLE_SPEC: pa_sample_spec = pa_sample_spec {
@@ -17,6 +26,21 @@ const DEFAULT_SAMPLE_SPEC: pa_sample_spec = pa_sample_spec {
 };
 
 
+/// The frame rate the animation is capped at unless told otherwise
+const DEFAULT_TARGET_FPS: u32 = 60;
+
+
+/// How many FFT windows of audio the capture thread holds onto before it
+/// starts dropping the oldest
+const CAPTURE_WINDOWS: usize = 4;
+
+
+/// The FFT sizes the runner picks between as the window is resized. The
+/// smallest is the size it starts with.
+const MIN_FFT_SIZE: usize = 1024;
+const MAX_FFT_SIZE: usize = 16384;
+
+
 #[derive(Clone)]
 /// The culmination of all of the visualizer parts
 pub struct VizRunner<'a> {
@@ -27,8 +51,35 @@ pub struct VizRunner<'a> {
 impl<'a> VizRunner<'a> {
     /// Create a new visuaizer
     pub fn new(mainloop: &'a PulseAudioMainloop) ->  VizRunner<'a> {
+        let pipeline = FramePipeline::new(Some(DEFAULT_TARGET_FPS));
+        VizRunner::with_output(mainloop, AudioOutput::Inline(Box::new(pipeline)))
+    }
+
+    /// Create a new visualizer that renders on its own thread. The PulseAudio
+    /// callbacks only copy the raw audio into a ring buffer, so a slow FFT or
+    /// terminal can't stall capture. The AudioFft and the Visualizer are both
+    /// created on and owned by the render thread.
+    pub fn new_threaded(mainloop: &'a PulseAudioMainloop, target_fps: Option<u32>) -> VizRunner<'a> {
+        // The ring is sized from the FFT, which only exists on the render
+        // thread, so the sending end is handed back once it's been made
+        let (sender_tx, sender_rx) = mpsc::channel();
+        let (fps_tx, fps_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut pipeline = FramePipeline::new(target_fps);
+            let window = pipeline.fft.get_buf_size();
+            let frame_size = pipeline.fft.get_frame_size();
+            let (sender, receiver) = capture::capture_ring(window * CAPTURE_WINDOWS, frame_size);
+            sender_tx.send(sender).unwrap();
+            pipeline.run(receiver, window, fps_rx);
+        });
+        let sender = sender_rx.recv().unwrap();
+        VizRunner::with_output(mainloop, AudioOutput::Threaded(sender, fps_tx))
+    }
+
+    /// Create the runner and connect to PulseAudio
+    fn with_output(mainloop: &'a PulseAudioMainloop, output: AudioOutput) -> VizRunner<'a> {
         let vzr = VizRunner {
-            internal: Rc::new(RefCell::new(VizRunnerInternal::new(mainloop)))
+            internal: Rc::new(RefCell::new(VizRunnerInternal::new(mainloop, output)))
         };
         {
             let clone = vzr.clone();
@@ -38,13 +89,201 @@ impl<'a> VizRunner<'a> {
         }
         vzr
     }
+
+    /// Set the frame rate to cap the animation at. None disables the cap.
+    /// A threaded visualizer picks the new rate up before its next frame.
+    pub fn set_target_fps(&self, target_fps: Option<u32>) {
+        match self.internal.borrow_mut().output {
+            AudioOutput::Inline(ref mut pipeline) => pipeline.limiter.set_target_fps(target_fps),
+            // The render thread only goes away along with the runner
+            AudioOutput::Threaded(_, ref fps_sender) => { let _ = fps_sender.send(target_fps); }
+        }
+    }
+}
+
+
+/// Where the captured audio goes
+enum AudioOutput {
+    /// Straight into the FFT, rendering from inside the PulseAudio callback
+    Inline(Box<FramePipeline>),
+    /// Into the ring buffer read by the render thread, which is sent changes
+    /// to the frame rate cap down the channel
+    Threaded(CaptureSender, mpsc::Sender<Option<u32>>)
+}
+
+
+/// Turns raw audio into frames on the screen
+struct FramePipeline<S: Screen = Window> {
+    fft: AudioFft,
+    viz: visualizer::Visualizer<S>,
+    limiter: FrameLimiter
+}
+
+
+impl FramePipeline<Window> {
+    /// Create a new FramePipeline with the settings saved in the config file,
+    /// if there is one. This sets up the terminal, so it has to be created on
+    /// the thread that will do the rendering.
+    fn new(target_fps: Option<u32>) -> FramePipeline<Window> {
+        let mut pipeline = FramePipeline::with_visualizer(visualizer::Visualizer::new(), target_fps);
+        // A missing or broken config just leaves the defaults
+        if let Some(Ok(config)) = config::default_path().map(VisualizerConfig::load) {
+            pipeline.apply_config(&config);
+        }
+        pipeline
+    }
+}
+
+
+impl<S: Screen> FramePipeline<S> {
+    /// Create a new FramePipeline that draws with viz, using the default
+    /// settings
+    fn with_visualizer(viz: visualizer::Visualizer<S>, target_fps: Option<u32>) -> FramePipeline<S> {
+        let mut pipeline = FramePipeline {
+            fft: AudioFft::new(MIN_FFT_SIZE, DEFAULT_SAMPLE_SPEC.channels as usize),
+            viz: viz,
+            limiter: FrameLimiter::new(target_fps)
+        };
+        pipeline.viz.set_sample_rate(DEFAULT_SAMPLE_SPEC.rate as f64);
+        let silence = pipeline.fft.get_silence_level();
+        pipeline.viz.set_silence_level(silence);
+        pipeline
+    }
+
+    /// Apply a config to the visualizer and the FFT. The StereoSplit layout
+    /// needs exactly two channels, so a pipeline with any other number falls
+    /// back to plain bars.
+    fn apply_config(&mut self, config: &VisualizerConfig) {
+        config.apply(&mut self.viz, &mut self.fft);
+        if self.viz.get_layout() == Layout::StereoSplit && self.fft.get_channel_count() != 2 {
+            self.viz.set_layout(Layout::Bars);
+        }
+    }
+
+    /// Render windows of audio from the capture thread until it goes away,
+    /// capping the frame rate at the latest one sent down target_fps
+    fn run(&mut self, receiver: CaptureReceiver, window: usize, target_fps: mpsc::Receiver<Option<u32>>) {
+        while let Some(data) = receiver.recv_window(window) {
+            while let Ok(fps) = target_fps.try_recv() {
+                self.limiter.set_target_fps(fps);
+            }
+            // The ring only hands out whole frames, so this can't fail
+            let _ = self.feed(&data[..]);
+        }
+    }
+
+    /// Feed raw audio into the FFT, running a frame each time it fills up.
+    /// The audio has to be a whole number of frames. Anything else is
+    /// refused without feeding any of it, so the channels stay in step.
+    fn feed(&mut self, data: &[u8]) -> Result<(), FftError> {
+        let mut fed_count: usize = 0;
+        while fed_count < data.len() {
+            fed_count += try!(self.fft.feed_frames(&data[fed_count..]));
+            if fed_count < data.len() {
+                self.on_fft_frame_ready();
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry out an action the user asked for with the keyboard
+    fn apply_action(&mut self, action: Action) {
+        match action {
+            Action::CycleWindow => {
+                let window = controls::next_window(self.fft.get_window_function());
+                self.fft.set_window_function(window);
+            },
+            Action::CycleColors => {
+                let color_scheme = controls::next_color_scheme(self.viz.get_color_scheme());
+                self.viz.set_color_scheme(color_scheme);
+            },
+            Action::TogglePause => {
+                let paused = self.viz.is_paused();
+                self.viz.set_paused(!paused);
+            },
+            Action::IncreaseSmoothing => {
+                let smoothing = self.viz.get_spectrum_smoothing();
+                self.viz.set_spectrum_smoothing(smoothing + 1);
+            },
+            Action::DecreaseSmoothing => {
+                let smoothing = self.viz.get_spectrum_smoothing();
+                if smoothing > 0 {
+                    self.viz.set_spectrum_smoothing(smoothing - 1);
+                }
+            },
+            Action::SaveConfig => {
+                // There's nowhere to report a failure while the terminal is
+                // taken over, so a config that can't be saved is skipped
+                if let Some(path) = config::default_path() {
+                    let _ = VisualizerConfig::capture(&self.viz, &self.fft).save(path);
+                }
+            }
+        }
+    }
+
+    /// Called whenever the FFT has enough data to run a frame of the visualizer
+    fn on_fft_frame_ready(&mut self) {
+        // Reading keys is also what picks up terminal resizes
+        while let Some(key) = self.viz.read_key() {
+            if let Some(action) = controls::key_action(key) {
+                self.apply_action(action);
+            }
+        }
+
+        // Keep enough bins for every band the window now has room for
+        let fft_size = fft_size_for_bands(self.viz.get_band_count());
+        if fft_size != self.fft.get_fft_size() {
+            // The audio in the FFT was for the old size, so start filling the
+            // new one instead of drawing it
+            self.fft.resize(fft_size).unwrap();
+            return;
+        }
+
+        if self.viz.is_paused() {
+            // Keep draining the stream so it's fresh when unpaused, and keep
+            // the frozen frame on screen in case the terminal was resized
+            self.fft.discard_input();
+            self.limiter.wait();
+            self.viz.redraw().unwrap();
+            return;
+        }
+
+        self.fft.execute();
+        self.fft.compute_output();
+        self.limiter.wait();
+        self.render_output().unwrap();
+    }
+
+    /// Draw the FFT's latest output. The StereoSplit layout draws each
+    /// channel's own spectrum and every other layout draws the mixed one.
+    fn render_output(&mut self) -> Result<(), RenderError> {
+        if self.viz.get_layout() != Layout::StereoSplit {
+            return self.viz.render_frame(self.fft.get_output());
+        }
+
+        let fft = &self.fft;
+        let channels: Vec<&[f64]> = (0..fft.get_channel_count())
+            .filter_map(|channel| fft.get_channel_output(channel))
+            .collect();
+        self.viz.render_channels(&channels[..])
+    }
+}
+
+
+/// Get the smallest FFT size from MIN_FFT_SIZE to MAX_FFT_SIZE with at least
+/// one bin for each of band_count bands
+fn fft_size_for_bands(band_count: usize) -> usize {
+    let mut fft_size = MIN_FFT_SIZE;
+    while fft_size / 2 < band_count && fft_size < MAX_FFT_SIZE {
+        fft_size *= 2;
+    }
+    fft_size
 }
 
 
 struct VizRunnerInternal<'a> {
     context: Context<'a>,
-    fft: AudioFft,
-    viz: visualizer::Visualizer,
+    output: AudioOutput,
     external: Option<VizRunner<'a>>,
     stream: Option<PulseAudioStream<'a>>,
 }
@@ -52,12 +291,11 @@ struct VizRunnerInternal<'a> {
 
 impl<'a> VizRunnerInternal<'a> {
     /// Create a new instance of the VizRunnerInternal struct
-    fn new(mainloop: &'a PulseAudioMainloop) -> VizRunnerInternal<'a> {
+    fn new(mainloop: &'a PulseAudioMainloop, output: AudioOutput) -> VizRunnerInternal<'a> {
         let context = mainloop.create_context("rs_client");
         VizRunnerInternal {
             context: context,
-            fft: AudioFft::new(1024, 2),
-            viz: visualizer::Visualizer::new(),
+            output: output,
             external: None,
             stream: None
         }
@@ -144,13 +382,6 @@ impl<'a> VizRunnerInternal<'a> {
         self.stream = Some(stream);
     }
 
-    /// Called whenever the FFT has enough data to run a frame of the visualizer
-    fn on_fft_frame_ready(&mut self) {
-        self.fft.execute();
-        self.fft.compute_output();
-        self.viz.render_frame(self.fft.get_output()).unwrap();
-    }
-
     /// Handles a stale stream and returns true if the stream was stale
     /// A stale stream can occur when switching streams before the current
     /// stream was in the "ready" state. Rather than wasting waiting for the
@@ -183,12 +414,14 @@ impl<'a> VizRunnerInternal<'a> {
 
         match stream.peek() {
             Ok(data) => {
-                let mut fed_count: usize = 0;
-                while fed_count < data.len() {
-                    fed_count += self.fft.feed_u8_data(data);
-                    if fed_count < data.len() {
-                        self.on_fft_frame_ready();
-                    }
+                match self.output {
+                    AudioOutput::Inline(ref mut pipeline) => {
+                        // PulseAudio only hands out whole frames, but if a
+                        // fragment is ever cut short it's dropped rather than
+                        // throwing the channels out of step
+                        let _ = pipeline.feed(data);
+                    },
+                    AudioOutput::Threaded(ref sender, _) => sender.push(data)
                 }
             },
             Err(_) => return
@@ -197,3 +430,80 @@ impl<'a> VizRunnerInternal<'a> {
         stream.drop_fragment().unwrap();
     }
 }
+
+
+/// A screen that throws away everything drawn on it
+#[cfg(test)]
+struct NullScreen;
+
+
+#[cfg(test)]
+impl Screen for NullScreen {
+    fn addstr(&mut self, _: c_int, _: c_int, _: &str) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn addbytes(&mut self, _: c_int, _: c_int, _: &[c_char]) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn noutrefresh(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn doupdate(&mut self) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+
+    fn get_max_yx(&self) -> Result<(c_int, c_int), c_int> {
+        Ok((24, 81))
+    }
+
+    fn getch(&mut self) -> Option<c_int> {
+        None
+    }
+
+    fn color_set(&mut self, _: c_short) -> Result<c_int, c_int> {
+        Ok(0)
+    }
+}
+
+
+#[test]
+fn test_stereo_split_config() {
+    let config = VisualizerConfig::parse("layout = \"stereo_split\"\n").unwrap();
+    let mut pipeline = FramePipeline::with_visualizer(visualizer::Visualizer::with_screen(NullScreen), None);
+    pipeline.apply_config(&config);
+    assert_eq!(pipeline.viz.get_layout(), Layout::StereoSplit);
+
+    // A bit more than a window of audio runs one frame, which has to draw
+    // each channel on its own side
+    let window = pipeline.fft.get_buf_size();
+    assert_eq!(pipeline.feed(&vec![1; window + 4][..]), Ok(()));
+    assert_eq!(pipeline.render_output(), Ok(()));
+
+    // A mono pipeline can't be split
+    pipeline.fft = AudioFft::new(1024, 1);
+    pipeline.apply_config(&config);
+    assert_eq!(pipeline.viz.get_layout(), Layout::Bars);
+}
+
+
+#[test]
+fn test_feed_partial_frame() {
+    // A buffer ending part way through a frame is refused instead of
+    // feeding the whole samples forever
+    let mut pipeline = FramePipeline::with_visualizer(visualizer::Visualizer::with_screen(NullScreen), None);
+    let window = pipeline.fft.get_buf_size();
+    assert_eq!(pipeline.feed(&vec![1; window + 3][..]), Err(FftError::PartialFrame(window + 3, 4)));
+    assert_eq!(pipeline.feed(&vec![1; window + 4][..]), Ok(()));
+}
+
+
+#[test]
+fn test_fft_size_for_bands() {
+    assert_eq!(fft_size_for_bands(80), 1024);
+    assert_eq!(fft_size_for_bands(512), 1024);
+    assert_eq!(fft_size_for_bands(513), 2048);
+    assert_eq!(fft_size_for_bands(100000), 16384);
+}